@@ -0,0 +1,122 @@
+//! Optional log of backend exchanges, for attaching to bug reports after the backend changes
+
+use std::path::{Path, PathBuf};
+
+use reqwest::header::{HeaderMap, AUTHORIZATION, COOKIE, SET_COOKIE};
+use reqwest::StatusCode;
+use tokio::io::AsyncWriteExt;
+
+/// Name of the capture log inside the config directory
+pub const CAPTURE_FILE: &str = "debug-capture.log";
+/// Response bytes kept per entry; the rest is only counted
+const MAX_BODY: usize = 2048;
+/// Size at which the log is rotated to `<name>.1`, replacing the previous one
+const MAX_LOG_SIZE: u64 = 1024 * 1024;
+const REDACTED: &str = "***";
+
+/// Appends formatted exchanges to a size-capped log file
+#[derive(Debug, Clone)]
+pub struct DebugCapture {
+    file: PathBuf,
+    /// Headers sent with every backend request
+    headers: HeaderMap,
+}
+
+impl DebugCapture {
+    pub fn new(file: PathBuf, headers: HeaderMap) -> Self {
+        Self { file, headers }
+    }
+
+    /// Log one exchange; failing to write the log never fails the request
+    pub async fn record(&self, url: &str, status: StatusCode, body: &[u8]) {
+        let entry = format_exchange(url, &self.headers, status, body);
+        let _ = append(&self.file, &entry).await;
+    }
+}
+
+/// Human-readable entry for one GET request and its response, secrets masked
+pub fn format_exchange(url: &str, headers: &HeaderMap, status: StatusCode, body: &[u8]) -> String {
+    let mut entry = format!("--- GET {}\n", url);
+    for (name, value) in headers {
+        let value = if [COOKIE, AUTHORIZATION, SET_COOKIE].contains(name) {
+            REDACTED.into()
+        } else {
+            String::from_utf8_lossy(value.as_bytes())
+        };
+        entry.push_str(&format!("> {}: {}\n", name, value));
+    }
+    entry.push_str(&format!("< {}\n", status));
+
+    entry.push_str(&String::from_utf8_lossy(&body[..body.len().min(MAX_BODY)]));
+    if body.len() > MAX_BODY {
+        entry.push_str(&format!("\n[... {} more bytes]", body.len() - MAX_BODY));
+    }
+    entry.push_str("\n\n");
+    entry
+}
+
+async fn append(file: &Path, entry: &str) -> std::io::Result<()> {
+    if let Some(dir) = file.parent() {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+    if tokio::fs::metadata(file)
+        .await
+        .is_ok_and(|m| m.len() >= MAX_LOG_SIZE)
+    {
+        let mut rotated = file.as_os_str().to_owned();
+        rotated.push(".1");
+        tokio::fs::rename(file, rotated).await?;
+    }
+
+    let mut log = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(file)
+        .await?;
+    log.write_all(entry.as_bytes()).await?;
+    log.flush().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::{HeaderValue, REFERER};
+
+    #[test]
+    fn test_format_exchange_redacts_and_truncates() {
+        let mut headers = HeaderMap::new();
+        headers.insert(REFERER, HeaderValue::from_static("https://v1.y2mate.nu/"));
+        headers.insert(COOKIE, HeaderValue::from_static("session=secret"));
+        let body = format!(r#"{{"error":0,"title":"{}"}}"#, "x".repeat(MAX_BODY));
+
+        let entry = format_exchange(
+            "https://backend.example/init?a=1",
+            &headers,
+            StatusCode::OK,
+            body.as_bytes(),
+        );
+
+        assert!(entry.starts_with("--- GET https://backend.example/init?a=1\n"));
+        assert!(entry.contains("> referer: https://v1.y2mate.nu/\n"));
+        assert!(entry.contains("> cookie: ***\n"));
+        assert!(!entry.contains("secret"));
+        assert!(entry.contains("< 200 OK\n{\"error\":0,"));
+        assert!(entry.contains(&format!("[... {} more bytes]", body.len() - MAX_BODY)));
+    }
+
+    #[tokio::test]
+    async fn test_capture_rotates_full_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join(CAPTURE_FILE);
+        std::fs::write(&file, vec![b'.'; MAX_LOG_SIZE as usize]).unwrap();
+
+        let capture = DebugCapture::new(file.clone(), HeaderMap::new());
+        capture
+            .record("https://backend.example/init", StatusCode::OK, b"{}")
+            .await;
+
+        let log = std::fs::read_to_string(&file).unwrap();
+        assert!(log.starts_with("--- GET https://backend.example/init"));
+        assert!(dir.path().join(format!("{}.1", CAPTURE_FILE)).exists());
+    }
+}
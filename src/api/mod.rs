@@ -1,4 +1,5 @@
+pub mod capture;
 mod client;
 pub mod models;
 
-pub use client::{ApiClient, Result};
+pub use client::{ApiClient, ApiError, Result};
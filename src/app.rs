@@ -1,20 +1,87 @@
-use std::path::PathBuf;
+use std::{
+    hash::{Hash, Hasher},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
-use futures::StreamExt;
-use iced::Task;
+use futures::{
+    channel::mpsc,
+    stream::{self, BoxStream},
+    StreamExt,
+};
+use iced::{clipboard, window, Subscription, Task};
+use tokio_util::sync::CancellationToken;
 
 use crate::{
-    api::ApiClient,
-    application::{DownloadCoordinator, DownloadEvent},
-    domain::{AppError, DownloadPhase, DownloadPlan},
-    ui::{DownloadMessage, DownloadView},
+    api::{
+        models::{ApiConfig, VideoInfo},
+        ApiClient,
+    },
+    application::{
+        append_to_playlist, transcode_stream, DownloadCoordinator, DownloadEvent, DownloadQueue,
+        DownloadRequest, FilenameTemplate, HistoryEntry, HistoryStore, ItemOutcome, PausedStore,
+        PrepareStage, ProgressAggregator, QueuedItem, RateMeter, ResumeRecord, SaveDirStore,
+        Settings, Sound, TeePolicy, TrackTags, TranscodeEvent, TranscodeParams,
+    },
+    domain::{AppError, AudioQuality, DownloadPhase, DownloadPlan, OutputFormat},
+    ui::{DownloadMessage, DownloadView, HistoryRow},
+    utils::{extract_video_id, format_bytes, RepeatCollapser},
 };
 
+/// Shown before downloading a single video out of a playlist link
+const PLAYLIST_PROMPT: &str = "This link is part of a playlist, but only its video will be \
+    downloaded. Paste the video links to get them all, or press Download again to continue.";
+const NETWORK_DOWN: &str = "Network appears down — paused";
+/// Past downloads listed in the view
+const HISTORY_SHOWN: usize = 50;
+/// Pause in typing before the URL's video is looked up
+const PREVIEW_DELAY: Duration = Duration::from_millis(400);
+
 pub struct DownloadApp {
     view: DownloadView,
     coordinator: DownloadCoordinator,
+    settings: Settings,
+    /// Where changed settings are written, `None` to keep them in memory only
+    settings_file: Option<PathBuf>,
     phase: DownloadPhase,
+    queue: DownloadQueue,
+    active_item: Option<QueuedItem>,
     active_plan: Option<DownloadPlan>,
+    /// Title of the item being downloaded, once its plan has been used up
+    active_title: Option<String>,
+    /// Tags to write into the item being downloaded once it's saved
+    active_tags: Option<TrackTags>,
+    /// Bytes the finished download wrote, before any transcoding or cover art
+    downloaded_size: Option<u64>,
+    /// Quality and format the finished download was requested in
+    downloaded_output: Option<(AudioQuality, OutputFormat)>,
+    errors: RepeatCollapser,
+    /// Latest status line, before the elapsed time is appended
+    status: String,
+    /// When the current prepare or download step started
+    step_started: Option<Instant>,
+    /// Bytes across the items of the current batch
+    progress: ProgressAggregator,
+    /// Speed of the running download
+    rate: RateMeter,
+    /// Album applied to every track of the current batch
+    album: Option<String>,
+    /// Input the playlist prompt was last shown for
+    playlist_prompted: Option<String>,
+    /// Cancelled to abort looking up or downloading the current item
+    cancel: Option<CancellationToken>,
+    /// Cancelled to pause the running download
+    pause: Option<CancellationToken>,
+    /// Download paused in this or an earlier session, waiting to be resumed
+    paused: Option<ResumeRecord>,
+    paused_store: Option<PausedStore>,
+    history: Option<HistoryStore>,
+    save_dir_store: Option<SaveDirStore>,
+    /// Why the configured backend or proxy can't be used; nothing is downloaded while set
+    config_error: Option<String>,
+    /// Status lines the coordinator reports while it works
+    status_feed: StatusFeed,
 }
 
 impl Default for DownloadApp {
@@ -24,14 +91,115 @@ impl Default for DownloadApp {
 }
 
 impl DownloadApp {
+    /// App with the settings saved in the user's config directory, written back on change
     pub fn new() -> Self {
-        let api_client = ApiClient::new(Default::default());
+        match Settings::default_file() {
+            Some(file) => Self::with_settings_file(file),
+            None => Self::with_settings(Settings::default()),
+        }
+    }
+
+    /// App with the settings in `file`, written back on change unless the file
+    /// couldn't be used as it is
+    pub fn with_settings_file(file: PathBuf) -> Self {
+        let (settings, warning) = Settings::load(&file);
+
+        let mut app = Self::with_settings(settings);
+        match warning {
+            // Saving now would replace what's in the file with the fallbacks
+            Some(warning) => {
+                app.view.status_message = format!("{}; changes won't be saved", warning)
+            }
+            None => app.settings_file = Some(file),
+        }
+        app
+    }
+
+    /// App with these settings, not kept in any file
+    pub fn with_settings(settings: Settings) -> Self {
+        // Until the settings name a usable backend and proxy, nothing is sent at all, so a
+        // client on the defaults is only there to be replaced
+        let (api_client, config_error) = match settings.api_client() {
+            Ok(client) => (client, None),
+            Err(e) => (
+                ApiClient::try_new(ApiConfig::default()).expect("default config is valid"),
+                Some(e.to_string()),
+            ),
+        };
+        let paused_store = PausedStore::open_default();
+        let paused = paused_store.as_ref().and_then(PausedStore::load);
+        let history = HistoryStore::open_default();
+        let quality = settings.quality;
+
+        let (status_tx, status_rx) = mpsc::unbounded();
+
+        let mut view = DownloadView {
+            compact: settings.compact,
+            post_download: settings.post_download,
+            format: settings.format,
+            quality,
+            use_output_dir: settings.output_dir.is_some(),
+            output_dir: settings.output_dir.clone(),
+            proxy: settings.api.proxy.clone().unwrap_or_default(),
+            paused_download: paused.as_ref().map(paused_label),
+            history: history.as_ref().map(history_rows).unwrap_or_default(),
+            ..Default::default()
+        };
+        if let Some(record) = &paused {
+            view.status_message = format!("Paused download found: {}", paused_label(record));
+        }
+        if let Some(e) = &config_error {
+            view.status_message = format!("{}; fix it in the settings to download", e);
+        }
 
         Self {
-            view: DownloadView::default(),
-            coordinator: DownloadCoordinator::new(api_client),
+            view,
+            coordinator: DownloadCoordinator::new(api_client)
+                .with_status_hook(move |status| {
+                    let _ = status_tx.unbounded_send(status.to_string());
+                })
+                .quiet(settings.quiet)
+                .save_dialog_timeout(settings.save_dialog_timeout)
+                .write_buffer_size(settings.write_buffer_size)
+                .max_bytes_per_sec(settings.max_bytes_per_sec)
+                .min_free_margin(settings.min_free_margin)
+                .max_per_host(settings.max_downloads_per_host)
+                .keep_existing_tags(settings.keep_existing_tags)
+                .strict_format(settings.strict_format)
+                .output_dir(settings.output_dir.clone())
+                .filename_template(
+                    FilenameTemplate::new(
+                        settings.filename_template.clone(),
+                        quality,
+                        settings.format,
+                    )
+                    .empty_name_fallback(settings.empty_name_fallback),
+                ),
+            settings,
+            settings_file: None,
             phase: DownloadPhase::Idle,
+            queue: DownloadQueue::default(),
+            active_item: None,
             active_plan: None,
+            active_title: None,
+            active_tags: None,
+            downloaded_size: None,
+            downloaded_output: None,
+            errors: RepeatCollapser::default(),
+            status: String::new(),
+            step_started: None,
+            progress: ProgressAggregator::default(),
+            rate: RateMeter::default(),
+            album: None,
+            playlist_prompted: None,
+            cancel: None,
+            pause: None,
+            paused,
+            paused_store,
+            history,
+            save_dir_store: SaveDirStore::open_default(),
+            config_error,
+            status_feed: StatusFeed(Arc::new(Mutex::new(Some(status_rx)))),
         }
     }
 }
@@ -42,6 +210,45 @@ pub enum Message {
     Prepared(Result<DownloadPlan, AppError>),
     SavePathChosen(Option<PathBuf>),
     Download(DownloadEvent),
+    Transcode(TranscodeEvent),
+    SettingsExported(Result<Option<PathBuf>, AppError>),
+    /// Folder picked to save downloads into, `None` if the picker was dismissed
+    OutputDirChosen(Option<PathBuf>),
+    AlbumResolved(Option<String>),
+    /// URL field unchanged for `PREVIEW_DELAY` since holding this
+    UrlSettled(String),
+    /// Preview info for the URL it was looked up for
+    InfoResolved(String, Result<VideoInfo, AppError>),
+    /// ID3 tags written into the saved file, or why not
+    Tagged(PathBuf, Result<(), AppError>),
+    /// Cover art embedding finished for the saved file
+    ThumbnailEmbedded(PathBuf, Result<(), AppError>),
+    /// Clipboard contents, `None` when it's empty or holds something other than text
+    ClipboardRead(Option<String>),
+    /// Once a second while preparing or downloading, to show the elapsed time
+    Tick,
+    /// Intermediate status line from the coordinator
+    Status(String),
+}
+
+/// Receiving end of the coordinator's status hook, handed to the subscription once
+#[derive(Clone)]
+struct StatusFeed(Arc<Mutex<Option<mpsc::UnboundedReceiver<String>>>>);
+
+impl Hash for StatusFeed {
+    // One feed per app, so the subscription never needs restarting
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        "status-feed".hash(state);
+    }
+}
+
+impl StatusFeed {
+    fn messages(&self) -> BoxStream<'static, Message> {
+        match self.0.lock().ok().and_then(|mut rx| rx.take()) {
+            Some(rx) => rx.map(Message::Status).boxed(),
+            None => stream::empty().boxed(),
+        }
+    }
 }
 
 pub fn update(app: &mut DownloadApp, message: Message) -> Task<Message> {
@@ -49,97 +256,461 @@ pub fn update(app: &mut DownloadApp, message: Message) -> Task<Message> {
         Message::Ui(ui_msg) => {
             app.view.update(ui_msg.clone());
 
-            if let DownloadMessage::DownloadPressed = ui_msg {
-                if app.phase == DownloadPhase::Downloading {
-                    return Task::none();
-                }
+            if let DownloadMessage::CompactToggled(compact) = ui_msg {
+                app.settings.compact = compact;
+                save_settings(app);
+                let size = app.view.layout().window_size();
 
-                app.phase = DownloadPhase::Preparing;
-                app.view.is_downloading = true;
-                app.view.download_progress = 0.0;
-                app.view.status_message = "Fetching download info...".to_string();
+                return window::latest().and_then(move |id| window::resize(id, size));
+            }
 
-                let coordinator = app.coordinator.clone();
-                let youtube_url = app.view.youtube_url.clone();
+            if let DownloadMessage::PostDownloadSelected(action) = ui_msg {
+                app.settings.post_download = action;
+                save_settings(app);
+                return Task::none();
+            }
+
+            if let DownloadMessage::ProxySubmitted = ui_msg {
+                apply_proxy(app);
+                return Task::none();
+            }
+
+            if let (
+                Some(error),
+                DownloadMessage::DownloadPressed
+                | DownloadMessage::ResumeDownloadPressed
+                | DownloadMessage::ResumeQueuePressed,
+            ) = (&app.config_error, &ui_msg)
+            {
+                app.view.status_message = format!("{}; fix it in the settings to download", error);
+                return Task::none();
+            }
+
+            if let DownloadMessage::FormatChanged(_) | DownloadMessage::BitrateChanged(_) = ui_msg {
+                app.coordinator = app
+                    .coordinator
+                    .clone()
+                    .output(app.view.quality, app.view.format);
+                if (app.settings.quality, app.settings.format)
+                    != (app.view.quality, app.view.format)
+                {
+                    app.settings.quality = app.view.quality;
+                    app.settings.format = app.view.format;
+                    save_settings(app);
+                }
+                // The previewed name carries the extension
+                if let (Some(title), Some(video_id)) = (
+                    &app.view.preview_title,
+                    extract_video_id(&app.view.youtube_url),
+                ) {
+                    app.view.preview_filename =
+                        Some(app.coordinator.preview_filename(title, &video_id));
+                }
+                return Task::none();
+            }
+
+            if let DownloadMessage::YoutubeUrlChanged(url) = ui_msg {
+                if extract_video_id(&url).is_none() {
+                    return Task::none();
+                }
 
                 return Task::perform(
-                    async move { coordinator.prepare_download(youtube_url).await },
-                    Message::Prepared,
+                    async move {
+                        tokio::time::sleep(PREVIEW_DELAY).await;
+                        url
+                    },
+                    Message::UrlSettled,
                 );
             }
-        }
-        Message::Prepared(result) => match result {
-            Ok(plan) => {
-                app.phase = DownloadPhase::AwaitingSavePath;
-                app.view.status_message =
-                    format!("Ready: {}. Please select save location...", plan.title);
-                app.active_plan = Some(plan.clone());
 
+            if let DownloadMessage::ExportSettingsPressed = ui_msg {
                 let coordinator = app.coordinator.clone();
-                let suggested_filename = plan.suggested_filename;
+                let json = app.settings.to_redacted_json();
 
                 return Task::perform(
-                    async move { coordinator.choose_save_path(suggested_filename).await },
-                    Message::SavePathChosen,
+                    async move {
+                        let Some(path) = coordinator
+                            .choose_export_path("settings.json".to_string())
+                            .await
+                        else {
+                            return Ok(None);
+                        };
+                        tokio::fs::write(&path, json).await.map_err(|e| {
+                            AppError::Io(format!("Failed to export settings: {}", e))
+                        })?;
+                        Ok(Some(path))
+                    },
+                    Message::SettingsExported,
                 );
             }
-            Err(e) => {
-                app.phase = DownloadPhase::Failed;
-                app.view.is_downloading = false;
-                app.view.download_progress = 0.0;
-                app.view.status_message = format_error("Failed to prepare download", &e);
+
+            if let DownloadMessage::PauseQueuePressed = ui_msg {
+                app.queue.pause();
+                sync_queue_view(app);
+                return Task::none();
+            }
+
+            if let DownloadMessage::ResumeQueuePressed = ui_msg {
+                app.queue.resume();
+                sync_queue_view(app);
+
+                if app.active_item.is_none() {
+                    return start_next(app);
+                }
+                return Task::none();
+            }
+
+            match ui_msg {
+                // Turning the folder on before one was chosen asks for it first
+                DownloadMessage::UseOutputDirToggled(true) if app.view.output_dir.is_none() => {
+                    return choose_output_dir(app);
+                }
+                DownloadMessage::UseOutputDirToggled(_) => {
+                    apply_output_dir(app);
+                    return Task::none();
+                }
+                DownloadMessage::ChooseOutputDirPressed => return choose_output_dir(app),
+                _ => {}
+            }
+
+            if let DownloadMessage::RedownloadPressed(url) = ui_msg {
+                return update(app, Message::Ui(DownloadMessage::YoutubeUrlChanged(url)));
+            }
+
+            if let DownloadMessage::PastePressed = ui_msg {
+                return clipboard::read().map(Message::ClipboardRead);
+            }
+
+            if let DownloadMessage::CancelPressed = ui_msg {
+                if let Some(cancel) = app.cancel.take() {
+                    cancel.cancel();
+                    // A running download confirms once its partial file is gone; a lookup just stops
+                    if app.phase != DownloadPhase::Downloading {
+                        return update(app, Message::Download(DownloadEvent::Cancelled));
+                    }
+                }
+                return Task::none();
+            }
+
+            if let DownloadMessage::PauseDownloadPressed = ui_msg {
+                if let Some(pause) = app.pause.take() {
+                    pause.cancel();
+                }
+                return Task::none();
+            }
+
+            if let DownloadMessage::ResumeDownloadPressed = ui_msg {
+                if app.phase == DownloadPhase::Downloading {
+                    return Task::none();
+                }
+                let Some(record) = app.paused.take() else {
+                    return Task::none();
+                };
+                if let Some(store) = &app.paused_store {
+                    store.clear();
+                }
+                app.view.paused_download = None;
+                return start_download(app, DownloadRequest::resume(record));
+            }
+
+            if let DownloadMessage::DownloadPressed = ui_msg {
+                // Enter in the URL field sends this too, even while the button is disabled
+                if app.view.is_downloading
+                    || app.phase == DownloadPhase::Downloading
+                    || app.view.youtube_url.trim().is_empty()
+                {
+                    return Task::none();
+                }
+
+                app.queue =
+                    DownloadQueue::from_input(&app.view.youtube_url, app.settings.number_tracks)
+                        .network_failure_limit(app.settings.network_failure_limit);
+
+                // Asked once per input; pressing Download again goes ahead
+                let input = Some(app.view.youtube_url.as_str());
+                if app.queue.lone_playlist_link().is_some()
+                    && app.playlist_prompted.as_deref() != input
+                {
+                    app.playlist_prompted = input.map(str::to_string);
+                    app.queue.clear();
+                    app.view.status_message = PLAYLIST_PROMPT.to_string();
+                    return Task::none();
+                }
+
+                app.album = None;
+                app.progress.clear();
+                app.view.overall_progress = None;
+
+                if app.settings.album_from_playlist {
+                    if let Some(playlist_id) = app.queue.playlist_id() {
+                        let coordinator = app.coordinator.clone();
+                        return Task::perform(
+                            async move { coordinator.playlist_title(playlist_id).await },
+                            Message::AlbumResolved,
+                        );
+                    }
+                }
+
+                return start_next(app);
+            }
+        }
+        Message::Status(status) => report_status(app, status),
+        Message::Tick => {
+            app.view.pulse();
+            refresh_status(app);
+        }
+        Message::ClipboardRead(content) => match pasted_input(content) {
+            Ok(input) => {
+                return update(app, Message::Ui(DownloadMessage::YoutubeUrlChanged(input)));
             }
+            Err(message) => app.view.status_message = message.to_string(),
         },
+        Message::UrlSettled(url) => {
+            // Still typing: a later message covers the newer text
+            if url != app.view.youtube_url || app.config_error.is_some() {
+                return Task::none();
+            }
+
+            let coordinator = app.coordinator.clone();
+            return Task::perform(
+                async move {
+                    let info = coordinator.preview_info(url.clone()).await;
+                    (url, info)
+                },
+                |(url, info)| Message::InfoResolved(url, info),
+            );
+        }
+        Message::InfoResolved(url, info) => {
+            // Ignore lookups for a URL the user has since changed
+            if url == app.view.youtube_url {
+                let video_id = extract_video_id(&url).unwrap_or_default();
+                let info = info.ok();
+                app.view.preview_filename = info
+                    .as_ref()
+                    .map(|info| app.coordinator.preview_filename(&info.title, &video_id));
+                app.view.preview_channel = info.as_ref().and_then(|info| info.channel.clone());
+                app.view.preview_title = info.map(|info| info.title);
+
+                let earlier = extract_video_id(&url)
+                    .zip(app.history.as_ref())
+                    .and_then(|(video_id, history)| history.find(&video_id));
+                if let Some(entry) = earlier {
+                    app.view.status_message =
+                        format!("Downloaded before: {}", entry.path.display());
+                }
+            }
+        }
+        Message::AlbumResolved(album) => {
+            app.album = album;
+            return start_next(app);
+        }
+        Message::Prepared(result) => return on_prepared(app, result),
         Message::SavePathChosen(path_opt) => match path_opt {
             Some(path) => {
                 if let Some(plan) = app.active_plan.take() {
-                    app.phase = DownloadPhase::Downloading;
-                    app.view.is_downloading = true;
-                    app.view.download_progress = 0.0;
-                    app.view.status_message = format!("Downloading to: {}", path.display());
+                    report_status(app, format!("Downloading to: {}", path.display()));
+                    let tags = TrackTags::from_plan(&plan, app.settings.default_artist.as_deref());
+                    app.active_tags = Some(tags);
+                    app.active_title = Some(plan.title.clone());
 
-                    return Task::stream(
-                        app.coordinator
-                            .download_stream(plan.download_url, path)
-                            .map(Message::Download),
-                    );
+                    let mut request = DownloadRequest::from_plan(plan, path.clone());
+                    if let Some(item) = &app.active_item {
+                        request = request.source(item.url.clone());
+                    }
+                    if let (Some(dir), Some(name)) = (&app.settings.backup_dir, path.file_name()) {
+                        let policy = if app.settings.backup_best_effort {
+                            TeePolicy::BestEffort
+                        } else {
+                            TeePolicy::FailFast
+                        };
+                        request = request.backups(vec![dir.join(name)], policy);
+                    }
+                    return start_download(app, request);
                 }
 
                 app.phase = DownloadPhase::Failed;
                 app.view.is_downloading = false;
                 app.view.status_message = "Missing download plan".to_string();
             }
-            None => {
-                app.phase = DownloadPhase::Idle;
-                app.active_plan = None;
-                app.view.is_downloading = false;
-                app.view.download_progress = 0.0;
-                app.view.status_message = "Download cancelled".to_string();
-            }
+            None => return update(app, Message::Download(DownloadEvent::Cancelled)),
         },
-        Message::Download(event) => match event {
-            DownloadEvent::Progress(progress) => {
-                app.phase = DownloadPhase::Downloading;
-                app.view.download_progress = progress;
+        Message::Download(event) => {
+            if let Some(item) = &app.active_item {
+                let aggregate = app.progress.update(item.index, &event);
+                if let (Some(aggregate), true) = (aggregate, app.queue.total() > 1) {
+                    app.view.overall_progress = aggregate.fraction;
+                }
+            }
 
-                if progress >= 1.0 {
-                    app.view.status_message = "Download complete, finalizing...".to_string();
-                } else {
-                    app.view.status_message = format!("Downloading: {:.1}%", progress * 100.0);
+            if app.settings.play_sounds {
+                if let Some(sound) = Sound::for_event(&event) {
+                    sound.play();
                 }
             }
-            DownloadEvent::Completed(path) => {
-                app.phase = DownloadPhase::Completed;
-                app.view.is_downloading = false;
-                app.view.download_progress = 0.0;
-                app.view.status_message = format!("Saved: {}", path.display());
+
+            if matches!(
+                event,
+                DownloadEvent::Completed { .. }
+                    | DownloadEvent::Paused(_)
+                    | DownloadEvent::Cancelled
+                    | DownloadEvent::Failed(_)
+            ) {
+                app.pause = None;
+                app.view.can_pause = false;
+                app.cancel = None;
+                app.view.can_cancel = false;
             }
-            DownloadEvent::Failed(error) => {
+
+            match event {
+                DownloadEvent::Preparing(stage) => match stage {
+                    PrepareStage::Init => {
+                        report_status(app, "Requesting conversion...".to_string());
+                    }
+                    PrepareStage::Converting => {
+                        report_status(app, "Converting...".to_string());
+                    }
+                    PrepareStage::Resolved(plan) => return on_prepared(app, Ok(plan)),
+                },
+                // Cancelling is the user's call, so it isn't reported as an error
+                DownloadEvent::Cancelled => cancel_batch(app),
+                DownloadEvent::Failed(error) if app.phase == DownloadPhase::Preparing => {
+                    return on_prepared(app, Err(error));
+                }
+                DownloadEvent::Received(bytes) => {
+                    app.phase = DownloadPhase::Downloading;
+                    app.view.progress_indeterminate = true;
+                    app.rate.record(Instant::now(), bytes);
+                    let status = format!(
+                        "Downloaded {}{}",
+                        format_bytes(bytes),
+                        transfer_stats(&app.rate, None)
+                    );
+                    report_status(app, status);
+                }
+                DownloadEvent::Progress { downloaded, total } => {
+                    let progress = downloaded as f32 / total as f32;
+                    app.phase = DownloadPhase::Downloading;
+                    app.view.progress_indeterminate = false;
+                    app.view.download_progress = progress;
+                    app.rate.record(Instant::now(), downloaded);
+
+                    if progress >= 1.0 {
+                        report_status(app, "Download complete, finalizing...".to_string());
+                    } else {
+                        let status = format!(
+                            "Downloading: {:.1}%{}",
+                            progress * 100.0,
+                            transfer_stats(&app.rate, Some(total))
+                        );
+                        report_status(app, status);
+                    }
+                }
+                DownloadEvent::Completed {
+                    path,
+                    size,
+                    quality,
+                    format,
+                } => {
+                    app.downloaded_size = Some(size);
+                    app.downloaded_output = Some((quality, format));
+                    // Opt-in, for when the backend can't deliver a small enough file; the result
+                    // is MP3, so it only applies to MP3 downloads, and not to those the backend
+                    // already delivered at or under the target
+                    let transcode = TranscodeParams::select(app.settings.transcode_bitrate)
+                        .filter(|_| format == OutputFormat::Mp3)
+                        .filter(|params| {
+                            quality.kbps().is_none_or(|kbps| kbps > params.bitrate_kbps)
+                        });
+                    if let Some(params) = transcode {
+                        report_status(
+                            app,
+                            format!("Transcoding to {} kbps...", params.bitrate_kbps),
+                        );
+                        return Task::stream(
+                            transcode_stream(path, params).map(Message::Transcode),
+                        );
+                    }
+
+                    return finalize(app, path);
+                }
+                DownloadEvent::Paused(record) => {
+                    // The rest of a batch waits along with the paused item
+                    app.queue.pause();
+                    sync_queue_view(app);
+                    app.active_item = None;
+                    app.phase = DownloadPhase::Idle;
+                    app.view.is_downloading = false;
+                    app.view.download_progress = 0.0;
+
+                    let saved = app.paused_store.as_ref().map(|store| store.save(&record));
+                    app.view.status_message = match saved {
+                        Some(Err(e)) => format!("Paused, but it won't survive a restart: {}", e),
+                        _ => format!(
+                            "Paused at {}: {}",
+                            format_bytes(record.downloaded_bytes),
+                            paused_label(&record)
+                        ),
+                    };
+                    app.view.paused_download = Some(paused_label(&record));
+                    app.paused = Some(record);
+                }
+                DownloadEvent::Failed(error) => {
+                    app.phase = DownloadPhase::Failed;
+                    app.view.is_downloading = false;
+                    app.view.download_progress = 0.0;
+                    report_error(app, "Download failed", &error);
+                    return continue_after_failure(app, &error);
+                }
+            }
+        }
+        Message::Transcode(event) => match event {
+            TranscodeEvent::Progress(processed) => {
+                report_status(app, format!("Transcoding... {}s", processed.as_secs()));
+            }
+            TranscodeEvent::Completed(path) => return finalize(app, path),
+            TranscodeEvent::Failed(error) => {
                 app.phase = DownloadPhase::Failed;
                 app.view.is_downloading = false;
                 app.view.download_progress = 0.0;
-                app.view.status_message = format_error("Download failed", &error);
+                report_error(app, "Transcoding failed", &error);
+                return continue_after_failure(app, &error);
+            }
+        },
+        Message::Tagged(path, written) => {
+            // The audio itself is fine, so only mention it
+            if let Err(e) = written {
+                app.view.status_message = format!("{} ({})", app.view.status_message, e);
+            }
+            return after_tagging(app, path);
+        }
+        Message::ThumbnailEmbedded(path, result) => {
+            let task = on_saved(app, path);
+            // A missing cover isn't worth failing an otherwise good download over, so just
+            // mention it, unless the next queued item has already taken over the status line
+            if let (Err(e), DownloadPhase::Completed) = (result, app.phase) {
+                app.view.status_message = format!(
+                    "{} ({})",
+                    app.view.status_message,
+                    format_error("no cover art", &e)
+                );
+            }
+            return task;
+        }
+        Message::OutputDirChosen(dir) => {
+            if dir.is_some() {
+                app.view.output_dir = dir;
+                app.view.use_output_dir = true;
+            } else if app.view.output_dir.is_none() {
+                app.view.use_output_dir = false;
+            }
+            apply_output_dir(app);
+        }
+        Message::SettingsExported(result) => match result {
+            Ok(Some(path)) => {
+                app.view.status_message = format!("Settings exported to: {}", path.display());
             }
+            Ok(None) => {}
+            Err(e) => report_error(app, "Export failed", &e),
         },
     }
 
@@ -150,6 +721,864 @@ pub fn view(app: &DownloadApp) -> iced::Element<'_, Message> {
     app.view.view().map(Message::Ui)
 }
 
+pub fn subscription(app: &DownloadApp) -> Subscription<Message> {
+    let status = Subscription::run_with(app.status_feed.clone(), StatusFeed::messages);
+    let tick = match app.phase {
+        DownloadPhase::Preparing | DownloadPhase::Downloading => {
+            iced::time::every(Duration::from_secs(1)).map(|_| Message::Tick)
+        }
+        _ => Subscription::none(),
+    };
+    Subscription::batch([status, tick])
+}
+
+/// Pop the next queued URL and start preparing it
+fn start_next(app: &mut DownloadApp) -> Task<Message> {
+    let next = app.queue.next_item();
+    sync_queue_view(app);
+
+    let Some(item) = next else {
+        app.active_item = None;
+        if app.queue.is_paused() {
+            app.view.status_message = format!("Queue paused ({} remaining)", app.queue.remaining());
+        }
+        return Task::none();
+    };
+
+    app.phase = DownloadPhase::Preparing;
+    app.step_started = Some(Instant::now());
+    app.view.is_downloading = true;
+    app.view.download_progress = 0.0;
+    app.view.progress_indeterminate = false;
+    let mut status = match app.queue.total() {
+        1 => "Fetching download info...".to_string(),
+        total => format!("Fetching download info ({} of {})...", item.index, total),
+    };
+    // Said here, as this replaces any status set while queueing
+    if app.queue.duplicates() > 0 {
+        status.push_str(&format!(
+            " ({} duplicate link(s) skipped)",
+            app.queue.duplicates()
+        ));
+    }
+    report_status(app, status);
+
+    let coordinator = app.coordinator.clone();
+    let youtube_url = item.url.clone();
+    app.active_item = Some(item);
+
+    // Stage events would be swallowed in quiet mode anyway
+    let messages = if app.settings.quiet {
+        futures::stream::once(async move { coordinator.prepare_download(youtube_url).await })
+            .map(Message::Prepared)
+            .boxed()
+    } else {
+        coordinator
+            .prepare_stream(youtube_url)
+            .map(Message::Download)
+            .boxed()
+    };
+
+    // Dropping the stream aborts whichever backend request is in flight
+    let cancel = CancellationToken::new();
+    app.cancel = Some(cancel.clone());
+    app.view.can_cancel = true;
+    Task::stream(messages.take_until(cancel.cancelled_owned()))
+}
+
+/// Drop the current item and whatever is still queued behind it
+fn cancel_batch(app: &mut DownloadApp) {
+    app.phase = DownloadPhase::Idle;
+    app.queue.clear();
+    sync_queue_view(app);
+    app.active_item = None;
+    app.active_plan = None;
+    app.view.is_downloading = false;
+    app.view.can_cancel = false;
+    app.view.download_progress = 0.0;
+    app.view.status_message = "Download cancelled".to_string();
+}
+
+/// Run `request`, pausable and cancellable from the view
+fn start_download(app: &mut DownloadApp, request: DownloadRequest) -> Task<Message> {
+    app.phase = DownloadPhase::Downloading;
+    app.step_started = Some(Instant::now());
+    app.view.is_downloading = true;
+    app.view.download_progress = 0.0;
+    app.view.progress_indeterminate = false;
+    app.rate.clear();
+
+    let pause = CancellationToken::new();
+    app.pause = Some(pause.clone());
+    app.view.can_pause = true;
+
+    let cancel = CancellationToken::new();
+    app.cancel = Some(cancel.clone());
+    app.view.can_cancel = true;
+
+    Task::stream(
+        app.coordinator
+            .download(request.pause_on(pause).cancel_on(cancel))
+            .map(Message::Download),
+    )
+}
+
+/// URL(s) to put in the input field from the clipboard, or why there are none
+fn pasted_input(content: Option<String>) -> Result<String, &'static str> {
+    let text = content.as_deref().map(str::trim).unwrap_or_default();
+    if text.is_empty() {
+        return Err("Clipboard is empty or not text");
+    }
+    if !text
+        .split_whitespace()
+        .all(|url| extract_video_id(url).is_some())
+    {
+        return Err("Clipboard doesn't hold a YouTube link");
+    }
+    Ok(text.to_string())
+}
+
+fn choose_output_dir(app: &mut DownloadApp) -> Task<Message> {
+    let coordinator = app.coordinator.clone();
+    Task::perform(
+        async move { coordinator.choose_folder().await },
+        Message::OutputDirChosen,
+    )
+}
+
+/// Save into the view's folder without asking if it's switched on, otherwise ask again
+fn apply_output_dir(app: &mut DownloadApp) {
+    let dir = app
+        .view
+        .output_dir
+        .clone()
+        .filter(|_| app.view.use_output_dir);
+    app.settings.output_dir = dir.clone();
+    app.coordinator = app.coordinator.clone().output_dir(dir);
+    save_settings(app);
+}
+
+/// Switch to the proxy typed into the view, keeping the current one if it's unusable
+fn apply_proxy(app: &mut DownloadApp) {
+    let proxy = Some(app.view.proxy.trim().to_string()).filter(|proxy| !proxy.is_empty());
+    let previous = std::mem::replace(&mut app.settings.api.proxy, proxy);
+
+    match app.settings.api_client() {
+        Ok(client) => {
+            app.coordinator = app.coordinator.clone().api_client(client);
+            app.config_error = None;
+            app.view.status_message = match &app.settings.api.proxy {
+                Some(_) => "Proxy set".to_string(),
+                None => "Proxy removed".to_string(),
+            };
+            save_settings(app);
+        }
+        Err(e) => {
+            app.settings.api.proxy = previous;
+            app.view.status_message = e.to_string();
+        }
+    }
+}
+
+/// Write the settings back after a change in the view; failing only costs remembering it
+fn save_settings(app: &mut DownloadApp) {
+    if let Some(file) = &app.settings_file {
+        if let Err(e) = app.settings.save(file) {
+            app.view.status_message = format!("Could not save settings: {}", e);
+        }
+    }
+}
+
+/// Most recent downloads, as listed in the view
+fn history_rows(history: &HistoryStore) -> Vec<HistoryRow> {
+    history
+        .recent(HISTORY_SHOWN)
+        .into_iter()
+        .map(|entry| HistoryRow {
+            title: entry.label(),
+            url: entry.url,
+        })
+        .collect()
+}
+
+/// File name of a paused download, as shown in the view
+fn paused_label(record: &ResumeRecord) -> String {
+    record
+        .path
+        .file_name()
+        .unwrap_or(record.path.as_os_str())
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn on_prepared(app: &mut DownloadApp, result: Result<DownloadPlan, AppError>) -> Task<Message> {
+    app.cancel = None;
+    app.view.can_cancel = false;
+
+    match result {
+        Ok(mut plan) => {
+            if let Some(item) = &app.active_item {
+                plan.suggested_filename = app.queue.filename_for(item, &plan.suggested_filename);
+            }
+            plan.album = app.album.clone();
+
+            app.phase = DownloadPhase::AwaitingSavePath;
+            let mut track =
+                TrackTags::from_plan(&plan, app.settings.default_artist.as_deref()).describe();
+            if let AudioQuality::Kbps(_) = plan.quality {
+                track = format!("{} ({})", track, plan.quality.label());
+            }
+            report_status(
+                app,
+                format!("Ready: {}. Please select save location...", track),
+            );
+            app.active_plan = Some(plan.clone());
+
+            if let Some(path) = app.coordinator.auto_save_path(&plan.suggested_filename) {
+                return update(app, Message::SavePathChosen(Some(path)));
+            }
+
+            let coordinator = app.coordinator.clone();
+            let suggested_filename = plan.suggested_filename;
+            let start_dir = app.save_dir_store.as_ref().and_then(SaveDirStore::load);
+
+            Task::perform(
+                async move {
+                    coordinator
+                        .choose_save_path(suggested_filename, start_dir)
+                        .await
+                },
+                Message::SavePathChosen,
+            )
+        }
+        Err(e) => {
+            app.phase = DownloadPhase::Failed;
+            app.view.is_downloading = false;
+            app.view.download_progress = 0.0;
+            report_error(app, "Failed to prepare download", &e);
+            continue_after_failure(app, &e)
+        }
+    }
+}
+
+/// Move on to the next queued item, unless the network looks down
+fn continue_after_failure(app: &mut DownloadApp, error: &AppError) -> Task<Message> {
+    if let Some(item) = &app.active_item {
+        let reason = error.to_string();
+        app.queue.record(item, ItemOutcome::Failed { reason });
+    }
+    if app.queue.note_connection(error.is_network()) {
+        app.view.status_message = NETWORK_DOWN.to_string();
+        sync_queue_view(app);
+        return Task::none();
+    }
+
+    if !app.queue.is_empty() {
+        return start_next(app);
+    }
+    finish_batch(app);
+    Task::none()
+}
+
+/// Post-processing after the audio is in place (and transcoded, if enabled)
+fn finalize(app: &mut DownloadApp, path: PathBuf) -> Task<Message> {
+    let youtube_url = app.active_item.as_ref().map(|item| item.url.clone());
+    let (Some(quality), Some(youtube_url), true) = (
+        app.settings.embed_thumbnail,
+        youtube_url,
+        TrackTags::applies_to(&path),
+    ) else {
+        return on_saved(app, path);
+    };
+
+    report_status(app, "Embedding cover art...".to_string());
+    let coordinator = app.coordinator.clone();
+    Task::perform(
+        async move {
+            let result = coordinator
+                .embed_thumbnail(youtube_url, path.clone(), quality)
+                .await;
+            (path, result)
+        },
+        |(path, result)| Message::ThumbnailEmbedded(path, result),
+    )
+}
+
+/// Final step of a successful download: report it and move on to the next queued item
+fn on_saved(app: &mut DownloadApp, path: PathBuf) -> Task<Message> {
+    app.phase = DownloadPhase::Completed;
+    app.view.is_downloading = false;
+    app.view.download_progress = 0.0;
+    let size = app.downloaded_size;
+    if let Some(item) = &app.active_item {
+        let bytes = size.unwrap_or_default();
+        app.queue.record(item, ItemOutcome::Succeeded { bytes });
+    }
+    app.view.status_message = match size {
+        Some(size) => format!("Saved: {} ({})", path.display(), format_bytes(size)),
+        None => format!("Saved: {}", path.display()),
+    };
+    app.errors.reset();
+    app.queue.note_connection(false);
+
+    if let (Some(store), Some(dir)) = (&app.save_dir_store, path.parent()) {
+        // Only a convenience for the next dialog, so a failed write is ignored
+        let _ = store.save(dir);
+    }
+
+    // The backend may deliver another container than asked for; ID3 tags would corrupt it
+    let tags = app
+        .active_tags
+        .take()
+        .filter(|_| TrackTags::applies_to(&path));
+    if let Some(tags) = tags {
+        return Task::perform(
+            async move {
+                let tagged = path.clone();
+                let written = tokio::task::spawn_blocking(move || tags.write_to(&tagged))
+                    .await
+                    .unwrap_or_else(|e| Err(AppError::Io(format!("Tagging failed: {}", e))));
+                (path, written)
+            },
+            |(path, written)| Message::Tagged(path, written),
+        );
+    }
+    after_tagging(app, path)
+}
+
+/// Rest of `on_saved`, once the file's tags are written
+fn after_tagging(app: &mut DownloadApp, path: PathBuf) -> Task<Message> {
+    let size = app.downloaded_size.take();
+    if let (Some(name), Some(dir)) = (&app.settings.playlist_file, path.parent()) {
+        let title = app.active_title.as_deref();
+        if let Err(e) = append_to_playlist(&dir.join(name), &path, title) {
+            app.view.status_message =
+                format!("{} (not added to {}: {})", app.view.status_message, name, e);
+        }
+    }
+
+    if let Err(e) = app.settings.post_download.run(&path) {
+        app.view.status_message = format!("{} (could not open it: {})", app.view.status_message, e);
+    }
+
+    let entry = app
+        .active_item
+        .as_ref()
+        .and_then(|item| HistoryEntry::new(&item.url, path, size))
+        .map(|entry| entry.title(app.active_title.clone()));
+    let entry = match (entry, app.downloaded_output.take()) {
+        (Some(entry), Some((quality, format))) => Some(entry.output(quality, format)),
+        (entry, _) => entry,
+    };
+    if let (Some(history), Some(entry)) = (&app.history, entry) {
+        // History is a convenience; a failed write shouldn't spoil a good download
+        let _ = history.append(&entry);
+        app.view.history = history_rows(history);
+    }
+
+    if !app.queue.is_empty() {
+        return start_next(app);
+    }
+    finish_batch(app);
+    Task::none()
+}
+
+/// Once the last item of a multi-link batch is done, show how the whole batch went
+fn finish_batch(app: &mut DownloadApp) {
+    if app.queue.total() <= 1 {
+        return;
+    }
+    let report = app.queue.report();
+    let mut summary = format!(
+        "Batch finished: {} of {} saved",
+        report.succeeded,
+        app.queue.total()
+    );
+    for failure in &report.failures {
+        summary.push_str(&format!("\nFailed {}: {}", failure.url, failure.reason));
+    }
+    app.view.status_message = summary;
+}
+
+fn sync_queue_view(app: &mut DownloadApp) {
+    app.view.queued = app.queue.remaining();
+    app.view.queue_paused = app.queue.is_paused() && !app.queue.is_empty();
+}
+
+/// Show an intermediate status line unless quiet mode is on
+fn report_status(app: &mut DownloadApp, message: String) {
+    if !app.settings.quiet {
+        app.status = message;
+        refresh_status(app);
+    }
+}
+
+/// Re-render the latest status line, with the time spent on the current step while one runs
+fn refresh_status(app: &mut DownloadApp) {
+    let running = matches!(
+        app.phase,
+        DownloadPhase::Preparing | DownloadPhase::Downloading
+    );
+    app.view.status_message = match app.step_started {
+        Some(started) if running && !app.settings.quiet => {
+            with_elapsed(&app.status, started.elapsed())
+        }
+        _ => app.status.clone(),
+    };
+}
+
+/// `status` followed by the elapsed time, e.g. "Converting... 12s"; nothing under a second
+fn with_elapsed(status: &str, elapsed: Duration) -> String {
+    match elapsed.as_secs() {
+        0 => status.to_string(),
+        _ => format!("{} {}", status, format_duration(elapsed)),
+    }
+}
+
+/// Whole seconds, e.g. "12s" or "2m 05s"
+fn format_duration(duration: Duration) -> String {
+    match duration.as_secs() {
+        secs @ 0..=59 => format!("{}s", secs),
+        secs => format!("{}m {:02}s", secs / 60, secs % 60),
+    }
+}
+
+/// Speed and time left, e.g. " (1.2 MB/s, ~15s remaining)"; empty until there's a rate
+fn transfer_stats(rate: &RateMeter, total: Option<u64>) -> String {
+    let Some(bytes_per_sec) = rate.bytes_per_sec() else {
+        return String::new();
+    };
+    let eta = match total.and_then(|total| rate.eta(total)) {
+        Some(eta) => format!("~{} remaining", format_duration(eta)),
+        None => "ETA unknown".to_string(),
+    };
+    format!(" ({}/s, {})", format_bytes(bytes_per_sec as u64), eta)
+}
+
+/// Show an error, collapsing identical consecutive ones (common in failing batches)
+fn report_error(app: &mut DownloadApp, prefix: &str, error: &AppError) {
+    app.view.status_message = app.errors.push(&format_error(prefix, error));
+}
+
 fn format_error(prefix: &str, error: &AppError) -> String {
     format!("{}: {}", prefix, error)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::PostDownloadAction;
+
+    #[test]
+    fn test_playlist_link_prompts_before_downloading() {
+        let mut app = DownloadApp::with_settings(Settings::default());
+        app.view.youtube_url =
+            "https://www.youtube.com/watch?v=z0vCwGUZe1I&list=PL9tY0BWXOZFs".to_string();
+
+        let _ = update(&mut app, Message::Ui(DownloadMessage::DownloadPressed));
+        assert_eq!(app.phase, DownloadPhase::Idle);
+        assert_eq!(app.view.status_message, PLAYLIST_PROMPT);
+        assert!(app.active_item.is_none());
+
+        // Confirmed by pressing again
+        let _ = update(&mut app, Message::Ui(DownloadMessage::DownloadPressed));
+        assert_eq!(app.phase, DownloadPhase::Preparing);
+    }
+
+    #[test]
+    fn test_single_video_link_downloads_right_away() {
+        let mut app = DownloadApp::with_settings(Settings::default());
+        app.view.youtube_url = "https://www.youtube.com/watch?v=z0vCwGUZe1I".to_string();
+
+        let _ = update(&mut app, Message::Ui(DownloadMessage::DownloadPressed));
+        assert_eq!(app.phase, DownloadPhase::Preparing);
+        assert_ne!(app.view.status_message, PLAYLIST_PROMPT);
+    }
+
+    #[test]
+    fn test_with_elapsed() {
+        assert_eq!(
+            with_elapsed("Converting...", Duration::from_millis(400)),
+            "Converting..."
+        );
+        assert_eq!(
+            with_elapsed("Converting...", Duration::from_secs(12)),
+            "Converting... 12s"
+        );
+        assert_eq!(
+            with_elapsed("Downloading: 40.0%", Duration::from_secs(125)),
+            "Downloading: 40.0% 2m 05s"
+        );
+    }
+
+    #[test]
+    fn test_transfer_stats() {
+        let start = Instant::now();
+        let mut rate = RateMeter::default();
+        assert_eq!(transfer_stats(&rate, Some(100)), "");
+
+        rate.record(start, 0);
+        rate.record(start + Duration::from_secs(2), 2 * 1024 * 1024);
+        assert_eq!(
+            transfer_stats(&rate, Some(32 * 1024 * 1024)),
+            " (1.0 MB/s, ~30s remaining)"
+        );
+        assert_eq!(transfer_stats(&rate, None), " (1.0 MB/s, ETA unknown)");
+    }
+
+    #[test]
+    fn test_pasted_input() {
+        assert_eq!(pasted_input(None), Err("Clipboard is empty or not text"));
+        assert_eq!(
+            pasted_input(Some(" \n".to_string())),
+            Err("Clipboard is empty or not text")
+        );
+        assert_eq!(
+            pasted_input(Some(" https://youtu.be/z0vCwGUZe1I\n".to_string())),
+            Ok("https://youtu.be/z0vCwGUZe1I".to_string())
+        );
+        assert_eq!(
+            pasted_input(Some("shopping list".to_string())),
+            Err("Clipboard doesn't hold a YouTube link")
+        );
+    }
+
+    #[test]
+    fn test_cancel_while_preparing_returns_to_idle() {
+        let mut app = DownloadApp::with_settings(Settings::default());
+        app.view.youtube_url = "https://youtu.be/z0vCwGUZe1I".to_string();
+
+        let _ = update(&mut app, Message::Ui(DownloadMessage::DownloadPressed));
+        assert_eq!(app.phase, DownloadPhase::Preparing);
+        assert!(app.view.can_cancel);
+        let cancel = app.cancel.clone().unwrap();
+
+        let _ = update(&mut app, Message::Ui(DownloadMessage::CancelPressed));
+        assert!(cancel.is_cancelled());
+        assert_eq!(app.phase, DownloadPhase::Idle);
+        assert!(app.active_item.is_none());
+        assert!(!app.view.is_downloading);
+        assert!(!app.view.can_cancel);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_during_download_waits_for_cleanup() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut app = DownloadApp::with_settings(Settings::default());
+        let _ = start_download(
+            &mut app,
+            DownloadRequest::new(
+                "https://cdn.example.com/file.mp3".to_string(),
+                dir.path().join("song.mp3"),
+            ),
+        );
+        assert!(app.view.can_cancel);
+        let cancel = app.cancel.clone().unwrap();
+
+        let _ = update(&mut app, Message::Ui(DownloadMessage::CancelPressed));
+        assert!(cancel.is_cancelled());
+        // Still downloading until the coordinator reports the partial file is gone
+        assert_eq!(app.phase, DownloadPhase::Downloading);
+
+        let _ = update(&mut app, Message::Download(DownloadEvent::Cancelled));
+        assert_eq!(app.phase, DownloadPhase::Idle);
+        assert!(!app.view.is_downloading);
+        assert_eq!(app.view.download_progress, 0.0);
+        assert_eq!(app.view.status_message, "Download cancelled");
+    }
+
+    #[test]
+    fn test_batch_shows_position_and_summarizes_failures() {
+        let mut app = DownloadApp::with_settings(Settings::default());
+        app.view.youtube_url = "not-a-link\nhttps://youtu.be/z0vCwGUZe1I".to_string();
+
+        let _ = update(&mut app, Message::Ui(DownloadMessage::DownloadPressed));
+        assert!(app
+            .view
+            .status_message
+            .starts_with("Fetching download info (1 of 2)..."));
+
+        let _ = update(&mut app, Message::Prepared(Err(AppError::InvalidInput)));
+        assert!(app
+            .view
+            .status_message
+            .starts_with("Fetching download info (2 of 2)..."));
+
+        let _ = update(
+            &mut app,
+            Message::Prepared(Err(AppError::Api("Error code: 1".to_string()))),
+        );
+        assert_eq!(
+            app.view.status_message,
+            format!(
+                "Batch finished: 0 of 2 saved\nFailed not-a-link: {}\n\
+                 Failed https://youtu.be/z0vCwGUZe1I: {}",
+                AppError::InvalidInput,
+                AppError::Api("Error code: 1".to_string())
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_coordinator_status_reaches_view() {
+        let mut settings = Settings::default();
+        settings.api.oembed_url = "http://127.0.0.1:9/oembed".to_string();
+        let mut app = DownloadApp::with_settings(settings);
+        let mut feed = app.status_feed.messages();
+
+        app.coordinator.playlist_title("PL123".to_string()).await;
+        let Some(message) = feed.next().await else {
+            panic!("no status reported");
+        };
+        let _ = update(&mut app, message);
+        assert_eq!(app.view.status_message, "Fetching playlist title...");
+    }
+
+    #[test]
+    fn test_broken_settings_file_is_never_overwritten() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("settings.toml");
+        std::fs::write(&file, "compact = \"very\"").unwrap();
+
+        let mut app = DownloadApp::with_settings_file(file.clone());
+        assert!(app.view.status_message.ends_with("changes won't be saved"));
+        let _ = update(
+            &mut app,
+            Message::Ui(DownloadMessage::PostDownloadSelected(
+                PostDownloadAction::OpenFile,
+            )),
+        );
+        assert_eq!(
+            std::fs::read_to_string(&file).unwrap(),
+            "compact = \"very\""
+        );
+
+        std::fs::write(&file, "quiet = true\n").unwrap();
+        let mut app = DownloadApp::with_settings_file(file.clone());
+        let _ = update(
+            &mut app,
+            Message::Ui(DownloadMessage::PostDownloadSelected(
+                PostDownloadAction::OpenFile,
+            )),
+        );
+        let saved = std::fs::read_to_string(&file).unwrap();
+        assert!(saved.contains("quiet = true"), "{}", saved);
+        assert!(saved.contains("OpenFile"), "{}", saved);
+    }
+
+    #[test]
+    fn test_proxy_is_applied_only_when_usable() {
+        let mut app = DownloadApp::with_settings(Settings::default());
+        let submit = |app: &mut DownloadApp, proxy: &str| {
+            let _ = update(
+                app,
+                Message::Ui(DownloadMessage::ProxyChanged(proxy.to_string())),
+            );
+            let _ = update(app, Message::Ui(DownloadMessage::ProxySubmitted));
+        };
+
+        submit(&mut app, "socks5://proxy.local:1080");
+        assert_eq!(
+            app.settings.api.proxy.as_deref(),
+            Some("socks5://proxy.local:1080")
+        );
+        assert_eq!(app.view.status_message, "Proxy set");
+
+        submit(&mut app, "proxy.local:1080");
+        assert_eq!(
+            app.settings.api.proxy.as_deref(),
+            Some("socks5://proxy.local:1080")
+        );
+        assert!(app.view.status_message.starts_with("Invalid proxy"));
+
+        submit(&mut app, " ");
+        assert_eq!(app.settings.api.proxy, None);
+        assert_eq!(app.view.status_message, "Proxy removed");
+    }
+
+    #[test]
+    fn test_unusable_proxy_blocks_downloads() {
+        let mut settings = Settings::default();
+        settings.api.proxy = Some("proxy.local:1080".to_string());
+        let mut app = DownloadApp::with_settings(settings);
+        assert!(app.view.status_message.starts_with("Invalid proxy"));
+
+        app.view.youtube_url = "https://youtu.be/z0vCwGUZe1I".to_string();
+        let _ = update(&mut app, Message::Ui(DownloadMessage::DownloadPressed));
+        assert_eq!(app.phase, DownloadPhase::Idle);
+        assert!(app.active_item.is_none());
+
+        let _ = update(
+            &mut app,
+            Message::Ui(DownloadMessage::ProxyChanged(
+                "http://proxy.local:8080".to_string(),
+            )),
+        );
+        let _ = update(&mut app, Message::Ui(DownloadMessage::ProxySubmitted));
+        let _ = update(&mut app, Message::Ui(DownloadMessage::DownloadPressed));
+        assert_eq!(app.phase, DownloadPhase::Preparing);
+    }
+
+    #[tokio::test]
+    async fn test_transcode_is_opt_in() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("song.mp3");
+        std::fs::write(&path, b"ID3").unwrap();
+        let completed = |quality| {
+            Message::Download(DownloadEvent::Completed {
+                path: path.clone(),
+                size: 3,
+                quality,
+                format: OutputFormat::Mp3,
+            })
+        };
+
+        // A picked bitrate is the backend's job, not a reason to re-encode
+        let mut app = DownloadApp::with_settings(Settings::default());
+        let _ = update(&mut app, completed(AudioQuality::Kbps(320)));
+        assert!(!app.status.starts_with("Transcoding"), "{}", app.status);
+
+        let mut app = DownloadApp::with_settings(Settings {
+            transcode_bitrate: Some(128),
+            ..Default::default()
+        });
+        let _ = update(&mut app, completed(AudioQuality::Source));
+        assert_eq!(app.status, "Transcoding to 128 kbps...");
+
+        // Already delivered at the target
+        app.status.clear();
+        let _ = update(&mut app, completed(AudioQuality::Kbps(128)));
+        assert!(!app.status.starts_with("Transcoding"), "{}", app.status);
+    }
+
+    #[tokio::test]
+    async fn test_skipped_duplicates_are_reported() {
+        let mut app = DownloadApp::with_settings(Settings::default());
+        app.view.youtube_url =
+            "https://youtu.be/z0vCwGUZe1I https://youtu.be/dQw4w9WgXcQ z0vCwGUZe1I".to_string();
+        let _ = update(&mut app, Message::Ui(DownloadMessage::DownloadPressed));
+
+        assert_eq!(
+            app.view.status_message,
+            "Fetching download info (1 of 2)... (1 duplicate link(s) skipped)"
+        );
+    }
+
+    #[test]
+    fn test_bitrate_choice_is_saved() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("settings.toml");
+        let mut app = DownloadApp::with_settings_file(file.clone());
+
+        let _ = update(
+            &mut app,
+            Message::Ui(DownloadMessage::BitrateChanged(AudioQuality::Kbps(128))),
+        );
+        assert_eq!(Settings::load(&file).0.quality, AudioQuality::Kbps(128));
+
+        let app = DownloadApp::with_settings_file(file);
+        assert_eq!(app.view.quality, AudioQuality::Kbps(128));
+    }
+
+    #[test]
+    fn test_format_choice_renames_preview() {
+        let mut app = DownloadApp::with_settings(Settings::default());
+        app.view.youtube_url = "https://youtu.be/z0vCwGUZe1I".to_string();
+        app.view.preview_title = Some("Song".to_string());
+
+        let _ = update(
+            &mut app,
+            Message::Ui(DownloadMessage::FormatChanged(OutputFormat::M4a)),
+        );
+        assert_eq!(app.view.preview_filename.as_deref(), Some("Song.m4a"));
+
+        let _ = update(
+            &mut app,
+            Message::Ui(DownloadMessage::FormatChanged(OutputFormat::Mp3)),
+        );
+        assert_eq!(app.view.preview_filename.as_deref(), Some("Song.mp3"));
+    }
+
+    #[test]
+    fn test_download_ignored_while_busy_or_empty() {
+        let mut app = DownloadApp::with_settings(Settings::default());
+        app.view.youtube_url = "  ".to_string();
+        let _ = update(&mut app, Message::Ui(DownloadMessage::DownloadPressed));
+        assert_eq!(app.phase, DownloadPhase::Idle);
+        assert!(app.active_item.is_none());
+
+        app.view.youtube_url = "https://youtu.be/z0vCwGUZe1I".to_string();
+        let _ = update(&mut app, Message::Ui(DownloadMessage::DownloadPressed));
+        assert_eq!(app.phase, DownloadPhase::Preparing);
+        let started = app.step_started;
+
+        // A second submit mustn't restart the lookup
+        std::thread::sleep(Duration::from_millis(1));
+        let _ = update(&mut app, Message::Ui(DownloadMessage::DownloadPressed));
+        assert_eq!(app.phase, DownloadPhase::Preparing);
+        assert_eq!(app.step_started, started);
+    }
+
+    #[tokio::test]
+    async fn test_default_folder_skips_save_dialog() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut app = DownloadApp::with_settings(Settings::default());
+        let _ = update(
+            &mut app,
+            Message::OutputDirChosen(Some(dir.path().to_path_buf())),
+        );
+        assert!(app.view.use_output_dir);
+        assert_eq!(app.settings.output_dir.as_deref(), Some(dir.path()));
+
+        app.active_item = Some(QueuedItem {
+            url: "https://youtu.be/z0vCwGUZe1I".to_string(),
+            index: 1,
+        });
+        let plan = DownloadPlan {
+            title: "Song".to_string(),
+            download_url: "https://cdn.example.com/file.mp3".to_string(),
+            suggested_filename: "Song.mp3".to_string(),
+            album: None,
+            quality: AudioQuality::Source,
+            format: OutputFormat::Mp3,
+        };
+        let _ = update(&mut app, Message::Prepared(Ok(plan)));
+        assert_eq!(app.phase, DownloadPhase::Downloading);
+        assert_eq!(
+            app.view.status_message,
+            format!("Downloading to: {}", dir.path().join("Song.mp3").display())
+        );
+
+        // Switched off, the folder is remembered but no longer used
+        let _ = update(
+            &mut app,
+            Message::Ui(DownloadMessage::UseOutputDirToggled(false)),
+        );
+        assert_eq!(app.view.output_dir.as_deref(), Some(dir.path()));
+        assert_eq!(app.settings.output_dir, None);
+        assert_eq!(app.coordinator.auto_save_path("Song.mp3"), None);
+    }
+
+    #[test]
+    fn test_redownload_refills_url_field() {
+        let mut app = DownloadApp::with_settings(Settings::default());
+        let url = "https://www.youtube.com/watch?v=z0vCwGUZe1I".to_string();
+
+        let _ = update(
+            &mut app,
+            Message::Ui(DownloadMessage::RedownloadPressed(url.clone())),
+        );
+        assert_eq!(app.view.youtube_url, url);
+        assert_eq!(app.phase, DownloadPhase::Idle);
+    }
+
+    #[test]
+    fn test_dismissed_save_dialog_is_a_cancellation() {
+        let mut app = DownloadApp::with_settings(Settings::default());
+        app.phase = DownloadPhase::AwaitingSavePath;
+
+        let _ = update(&mut app, Message::SavePathChosen(None));
+        assert_eq!(app.phase, DownloadPhase::Idle);
+        assert_eq!(app.view.status_message, "Download cancelled");
+    }
+}
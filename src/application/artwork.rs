@@ -0,0 +1,45 @@
+use std::path::Path;
+
+use id3::{
+    frame::{Picture, PictureType},
+    Tag, TagLike, Version,
+};
+
+use crate::domain::AppError;
+
+/// Store `image` as the front cover of the MP3 at `path`, keeping any existing tags
+pub fn embed_cover_art(path: &Path, image: &[u8]) -> Result<(), AppError> {
+    let mut tag = Tag::read_from_path(path).unwrap_or_default();
+
+    tag.remove_picture_by_type(PictureType::CoverFront);
+    tag.add_frame(Picture {
+        mime_type: "image/jpeg".to_string(),
+        picture_type: PictureType::CoverFront,
+        description: String::new(),
+        data: image.to_vec(),
+    });
+
+    tag.write_to_path(path, Version::Id3v24)
+        .map_err(|e| AppError::Io(format!("Failed to write cover art: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_embed_cover_art_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("song.mp3");
+        std::fs::write(&path, b"not really audio").unwrap();
+
+        embed_cover_art(&path, b"old").unwrap();
+        embed_cover_art(&path, b"jpeg").unwrap();
+
+        let tag = Tag::read_from_path(&path).unwrap();
+        let pictures: Vec<_> = tag.pictures().collect();
+        assert_eq!(pictures.len(), 1);
+        assert_eq!(pictures[0].picture_type, PictureType::CoverFront);
+        assert_eq!(pictures[0].data, b"jpeg");
+    }
+}
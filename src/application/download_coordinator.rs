@@ -1,162 +1,843 @@
-use std::path::PathBuf;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use futures::{stream::BoxStream, StreamExt};
-use tokio::io::AsyncWriteExt;
+use futures::{channel::mpsc, stream::BoxStream, SinkExt, StreamExt};
+use tokio::sync::OwnedSemaphorePermit;
+use tokio_util::sync::CancellationToken;
 
+use super::artwork::embed_cover_art;
+use super::host_limits::HostLimiter;
+use super::metadata::{apply_tag, read_existing_tag};
+use super::naming::{ensure_extension, resolve_unique_path, FilenameTemplate};
+use super::output::{
+    check_free_space, output_error, OutputWriter, WriteTuning, DEFAULT_MIN_FREE_MARGIN,
+    OUTPUT_DISAPPEARED,
+};
+use super::resume::{ResumeRecord, CHECKPOINT_INTERVAL};
+use super::tee::{Tee, TeeError, TeePolicy};
 use crate::{
-    api::ApiClient,
-    domain::{AppError, DownloadPlan},
-    utils::{extract_video_id, sanitize_filename},
+    api::{
+        models::{AudioFormat, ConvertOptions, ThumbnailQuality, VideoInfo},
+        ApiClient, ApiError,
+    },
+    domain::{AppError, AudioQuality, DownloadPlan, OutputFormat},
+    utils::{extract_video_id, format_bytes},
 };
 
+/// Events buffered between a download and a frontend that's slow to take them
+const EVENT_BUFFER: usize = 64;
+
+/// Base delay between reconnect attempts, multiplied by the attempt number
+const RECONNECT_DELAY: Duration = Duration::from_millis(500);
+
+/// Steps of resolving a URL into a `DownloadPlan`
+#[derive(Debug, Clone)]
+pub enum PrepareStage {
+    /// Fetching the auth token and convert URL
+    Init,
+    /// Waiting for the backend to convert the video
+    Converting,
+    Resolved(DownloadPlan),
+}
+
 #[derive(Debug, Clone)]
 pub enum DownloadEvent {
-    Progress(f32),
-    Completed(PathBuf),
+    Preparing(PrepareStage),
+    /// Bytes downloaded so far out of a known total
+    Progress {
+        downloaded: u64,
+        total: u64,
+    },
+    /// Bytes downloaded so far, when the total size isn't known
+    Received(u64),
+    /// Saved to `path`; `size` is the byte count downloaded, checked against the file
+    Completed {
+        path: PathBuf,
+        size: u64,
+        quality: AudioQuality,
+        format: OutputFormat,
+    },
+    /// Stopped on request; the record says where to pick it up again
+    Paused(ResumeRecord),
+    /// Called off by the user (save dialog dismissed or download cancelled); not a failure
+    Cancelled,
     Failed(AppError),
 }
 
+/// A file to download, and everything optional about how
+#[derive(Debug, Clone)]
+pub struct DownloadRequest {
+    url: String,
+    path: PathBuf,
+    source: Option<String>,
+    backups: Vec<PathBuf>,
+    policy: TeePolicy,
+    pause: Option<CancellationToken>,
+    cancel: Option<CancellationToken>,
+    quality: AudioQuality,
+    format: OutputFormat,
+    #[cfg(test)]
+    scripted: Option<ScriptedResponse>,
+}
+
+impl DownloadRequest {
+    pub fn new(url: String, path: PathBuf) -> Self {
+        Self {
+            url,
+            path,
+            source: None,
+            backups: Vec::new(),
+            policy: TeePolicy::default(),
+            pause: None,
+            cancel: None,
+            quality: AudioQuality::default(),
+            format: OutputFormat::default(),
+            #[cfg(test)]
+            scripted: None,
+        }
+    }
+
+    /// Serve the download from `chunks` instead of `url`, split exactly as given
+    #[cfg(test)]
+    fn scripted(mut self, total: Option<u64>, chunks: Vec<&'static [u8]>) -> Self {
+        self.scripted = Some(ScriptedResponse {
+            total,
+            chunks: chunks.into_iter().map(bytes::Bytes::from_static).collect(),
+        });
+        self
+    }
+
+    /// Download what `plan` resolved to into `path`, in the quality and format it was
+    /// requested in
+    pub fn from_plan(plan: DownloadPlan, path: PathBuf) -> Self {
+        Self {
+            quality: plan.quality,
+            format: plan.format,
+            ..Self::new(plan.download_url, path)
+        }
+    }
+
+    /// Continue a download that was paused earlier
+    pub fn resume(record: ResumeRecord) -> Self {
+        Self {
+            source: record.source,
+            ..Self::new(record.url, record.path)
+        }
+    }
+
+    /// YouTube URL the download was resolved from, used to refresh an expired link
+    pub fn source(mut self, youtube_url: String) -> Self {
+        self.source = Some(youtube_url);
+        self
+    }
+
+    /// Also write every chunk to each of the `backups`; `policy` decides whether a
+    /// failing copy aborts the download
+    pub fn backups(mut self, backups: Vec<PathBuf>, policy: TeePolicy) -> Self {
+        self.backups = backups;
+        self.policy = policy;
+        self
+    }
+
+    /// Pause the download, keeping the partial file, once `token` is cancelled
+    pub fn pause_on(mut self, token: CancellationToken) -> Self {
+        self.pause = Some(token);
+        self
+    }
+
+    /// Abandon the download and delete the partial file once `token` is cancelled
+    pub fn cancel_on(mut self, token: CancellationToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+}
+
 #[derive(Clone)]
 pub struct DownloadCoordinator {
     api_client: ApiClient,
+    status: StatusReporter,
+    save_dialog_timeout: Option<Duration>,
+    write_buffer_size: Option<usize>,
+    max_bytes_per_sec: Option<u64>,
+    min_free_margin: u64,
+    keep_existing_tags: bool,
+    strict_format: bool,
+    naming: FilenameTemplate,
+    /// Folder downloads are saved into without asking
+    output_dir: Option<PathBuf>,
+    hosts: HostLimiter,
 }
 
 impl DownloadCoordinator {
     pub fn new(api_client: ApiClient) -> Self {
-        Self { api_client }
+        Self {
+            api_client,
+            status: StatusReporter::default(),
+            save_dialog_timeout: None,
+            write_buffer_size: None,
+            max_bytes_per_sec: None,
+            min_free_margin: DEFAULT_MIN_FREE_MARGIN,
+            keep_existing_tags: false,
+            strict_format: false,
+            naming: FilenameTemplate::default(),
+            output_dir: None,
+            hosts: HostLimiter::default(),
+        }
+    }
+
+    /// Run at most `max` downloads at once against any one host (2 when unset)
+    pub fn max_per_host(mut self, max: Option<usize>) -> Self {
+        self.hosts = max.map_or_else(HostLimiter::default, HostLimiter::new);
+        self
+    }
+
+    /// When overwriting a file, carry its ID3 tags over to the new download
+    pub fn keep_existing_tags(mut self, keep: bool) -> Self {
+        self.keep_existing_tags = keep;
+        self
+    }
+
+    /// Fail a download that isn't in the requested format instead of keeping it under the
+    /// extension of what was actually delivered
+    pub fn strict_format(mut self, strict: bool) -> Self {
+        self.strict_format = strict;
+        self
+    }
+
+    /// Keep at least this much space free on the target disk after the download
+    pub fn min_free_margin(mut self, margin: Option<u64>) -> Self {
+        self.min_free_margin = margin.unwrap_or(DEFAULT_MIN_FREE_MARGIN);
+        self
+    }
+
+    /// Name suggested files with `naming` instead of the bare title
+    pub fn filename_template(mut self, naming: FilenameTemplate) -> Self {
+        self.naming = naming;
+        self
+    }
+
+    /// Save downloads straight into `dir` instead of asking where each one goes
+    pub fn output_dir(mut self, dir: Option<PathBuf>) -> Self {
+        self.output_dir = dir;
+        self
+    }
+
+    /// Where to save a download without asking, if an output folder is set and still there
+    ///
+    /// With no dialog to confirm overwriting, an existing file gets a numbered sibling instead.
+    pub fn auto_save_path(&self, suggested_filename: &str) -> Option<PathBuf> {
+        self.output_dir
+            .as_ref()
+            .filter(|dir| dir.is_dir())
+            .map(|dir| resolve_unique_path(&dir.join(suggested_filename)))
+    }
+
+    /// Request, name and save downloads in `quality` and `format`, keeping the template
+    pub fn output(mut self, quality: AudioQuality, format: OutputFormat) -> Self {
+        self.naming = self.naming.output(quality, format);
+        self
+    }
+
+    /// Override the output write buffer size (otherwise picked from the target filesystem)
+    pub fn write_buffer_size(mut self, size: Option<usize>) -> Self {
+        self.write_buffer_size = size;
+        self
+    }
+
+    /// Keep each download's average speed under `limit` bytes per second (no cap when unset)
+    pub fn max_bytes_per_sec(mut self, limit: Option<u64>) -> Self {
+        self.max_bytes_per_sec = limit.filter(|&limit| limit > 0);
+        self
+    }
+
+    /// Treat the save dialog as cancelled if nobody answers it within `timeout`
+    pub fn save_dialog_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.save_dialog_timeout = timeout;
+        self
+    }
+
+    /// Receive intermediate status lines (connecting, reconnecting, ...)
+    pub fn with_status_hook(mut self, hook: impl Fn(&str) + Send + Sync + 'static) -> Self {
+        self.status.hook = Some(Arc::new(hook));
+        self
+    }
+
+    /// Send all further requests through `api_client`, e.g. after the proxy changed
+    pub fn api_client(mut self, api_client: ApiClient) -> Self {
+        self.api_client = api_client;
+        self
+    }
+
+    /// Suppress all intermediate status lines; only the final event is reported
+    pub fn quiet(mut self, quiet: bool) -> Self {
+        self.status.quiet = quiet;
+        self
     }
 
     pub async fn prepare_download(&self, youtube_url: String) -> Result<DownloadPlan, AppError> {
         let video_id = extract_video_id(&youtube_url).ok_or(AppError::InvalidInput)?;
 
+        self.status
+            .report(&format!("Fetching download info for {}...", video_id));
+
         let (title, download_url) = self
             .api_client
-            .get_download_info(&video_id)
+            .get_download_info(
+                &video_id,
+                convert_options(self.naming.quality(), self.naming.format()),
+            )
             .await
-            .map_err(|e| AppError::Api(e.to_string()))?;
+            .map_err(api_error)?;
 
-        let suggested_filename = format!(
-            "{}.mp3",
-            sanitize_filename(&title).trim_matches(|c| c == '.' || c == ' ')
-        );
+        Ok(build_plan(title, download_url, &video_id, &self.naming))
+    }
 
-        Ok(DownloadPlan {
-            title,
-            download_url,
-            suggested_filename,
-        })
+    /// Resolve a URL like `prepare_download`, plus the file size if the server reports it
+    pub async fn fetch_metadata(
+        &self,
+        youtube_url: String,
+    ) -> Result<(DownloadPlan, Option<u64>), AppError> {
+        let plan = self.prepare_download(youtube_url).await?;
+        let size = self
+            .api_client
+            .download_size(&plan.download_url)
+            .await
+            .ok()
+            .flatten();
+
+        Ok((plan, size))
+    }
+
+    /// Like `prepare_download`, but reports each backend step as it starts
+    pub fn prepare_stream(&self, youtube_url: String) -> BoxStream<'static, DownloadEvent> {
+        let naming = self.naming.clone();
+        futures::stream::unfold(
+            PrepareState::Start {
+                client: self.api_client.clone(),
+                youtube_url,
+            },
+            move |state| {
+                let naming = naming.clone();
+                async move {
+                    match state {
+                        PrepareState::Start {
+                            client,
+                            youtube_url,
+                        } => match extract_video_id(&youtube_url) {
+                            Some(video_id) => Some((
+                                DownloadEvent::Preparing(PrepareStage::Init),
+                                PrepareState::Init {
+                                    client,
+                                    video_id,
+                                    resigned: false,
+                                },
+                            )),
+                            None => Some((
+                                DownloadEvent::Failed(AppError::InvalidInput),
+                                PrepareState::Finished,
+                            )),
+                        },
+                        PrepareState::Init {
+                            client,
+                            video_id,
+                            resigned,
+                        } => match client.init().await {
+                            Ok(convert_url) => Some((
+                                DownloadEvent::Preparing(PrepareStage::Converting),
+                                PrepareState::Converting {
+                                    client,
+                                    video_id,
+                                    convert_url,
+                                    resigned,
+                                },
+                            )),
+                            Err(e) => {
+                                Some((DownloadEvent::Failed(api_error(e)), PrepareState::Finished))
+                            }
+                        },
+                        PrepareState::Converting {
+                            client,
+                            video_id,
+                            convert_url,
+                            resigned,
+                        } => match client
+                            .resolve_download(
+                                &convert_url,
+                                &video_id,
+                                convert_options(naming.quality(), naming.format()),
+                            )
+                            .await
+                        {
+                            Ok((title, download_url)) => Some((
+                                DownloadEvent::Preparing(PrepareStage::Resolved(build_plan(
+                                    title,
+                                    download_url,
+                                    &video_id,
+                                    &naming,
+                                ))),
+                                PrepareState::Finished,
+                            )),
+                            // The signature from init went stale; fetch a fresh one and retry once
+                            Err(ApiError::SignatureExpired) if !resigned => Some((
+                                DownloadEvent::Preparing(PrepareStage::Init),
+                                PrepareState::Init {
+                                    client,
+                                    video_id,
+                                    resigned: true,
+                                },
+                            )),
+                            Err(e) => {
+                                Some((DownloadEvent::Failed(api_error(e)), PrepareState::Finished))
+                            }
+                        },
+                        PrepareState::Finished => None,
+                    }
+                }
+            },
+        )
+        .boxed()
     }
 
-    pub async fn choose_save_path(&self, suggested_filename: String) -> Option<PathBuf> {
-        rfd::AsyncFileDialog::new()
+    /// Title and channel lookup for previewing a URL before downloading it
+    pub async fn preview_info(&self, youtube_url: String) -> Result<VideoInfo, AppError> {
+        let video_id = extract_video_id(&youtube_url).ok_or(AppError::InvalidInput)?;
+
+        self.api_client
+            .fetch_video_info(&video_id)
+            .await
+            .map_err(api_error)
+    }
+
+    /// Filename the video `video_id` titled `title` would be suggested under
+    pub fn preview_filename(&self, title: &str, video_id: &str) -> String {
+        self.naming.render(title, video_id)
+    }
+
+    /// Download the video's thumbnail and embed it as cover art in the saved file
+    pub async fn embed_thumbnail(
+        &self,
+        youtube_url: String,
+        path: PathBuf,
+        quality: ThumbnailQuality,
+    ) -> Result<(), AppError> {
+        let video_id = extract_video_id(&youtube_url).ok_or(AppError::InvalidInput)?;
+
+        self.status.report("Fetching thumbnail...");
+        let image = self
+            .api_client
+            .fetch_thumbnail(&video_id, quality)
+            .await
+            .map_err(api_error)?;
+
+        tokio::task::spawn_blocking(move || embed_cover_art(&path, &image))
+            .await
+            .map_err(|e| AppError::Io(e.to_string()))?
+    }
+
+    /// Playlist title to use as the album, or `None` if it can't be looked up
+    pub async fn playlist_title(&self, playlist_id: String) -> Option<String> {
+        self.status.report("Fetching playlist title...");
+        self.api_client
+            .fetch_playlist_title(&playlist_id)
+            .await
+            .ok()
+    }
+
+    /// Ask where to save a download, restricted to the output format; a name typed
+    /// without the extension gets it appended
+    ///
+    /// The dialog opens in `start_dir` when given, otherwise wherever the OS picks.
+    pub async fn choose_save_path(
+        &self,
+        suggested_filename: String,
+        start_dir: Option<PathBuf>,
+    ) -> Option<PathBuf> {
+        let format = self.naming.format();
+        let mut dialog = rfd::AsyncFileDialog::new()
+            .add_filter(format.description(), &[format.extension()])
+            .set_file_name(&suggested_filename);
+        if let Some(dir) = start_dir {
+            dialog = dialog.set_directory(dir);
+        }
+        let dialog = dialog.save_file();
+
+        with_timeout(self.save_dialog_timeout, dialog)
+            .await
+            .map(|handle| ensure_extension(handle.path(), format.extension()))
+    }
+
+    /// Ask for a folder, e.g. to save downloads into without asking each time
+    pub async fn choose_folder(&self) -> Option<PathBuf> {
+        let dialog = rfd::AsyncFileDialog::new().pick_folder();
+
+        with_timeout(self.save_dialog_timeout, dialog)
+            .await
+            .map(|handle| handle.path().to_path_buf())
+    }
+
+    /// Ask where to save a non-audio file (e.g. exported settings), taking the name as is
+    pub async fn choose_export_path(&self, suggested_filename: String) -> Option<PathBuf> {
+        let dialog = rfd::AsyncFileDialog::new()
             .set_file_name(&suggested_filename)
-            .save_file()
+            .save_file();
+
+        with_timeout(self.save_dialog_timeout, dialog)
             .await
             .map(|handle| handle.path().to_path_buf())
     }
 
-    pub fn download_stream(&self, url: String, path: PathBuf) -> BoxStream<'static, DownloadEvent> {
+    /// Start a download, returning the channel its events arrive on
+    ///
+    /// This is what both the GUI and the CLI consume, so they see the same events in the same
+    /// order. Dropping the receiver abandons the download.
+    pub fn download(&self, request: DownloadRequest) -> mpsc::Receiver<DownloadEvent> {
+        let (mut tx, rx) = mpsc::channel(EVENT_BUFFER);
+        let mut events = self.event_stream(request);
+
+        tokio::spawn(async move {
+            while let Some(event) = events.next().await {
+                if tx.send(event).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        rx
+    }
+
+    fn event_stream(&self, request: DownloadRequest) -> BoxStream<'static, DownloadEvent> {
         futures::stream::unfold(
             DownloadRuntimeState::Start {
-                client: self.api_client.clone(),
-                url,
-                path,
+                ctx: DownloadContext {
+                    client: self.api_client.clone(),
+                    url: request.url,
+                    path: request.path,
+                    source: request.source,
+                    backups: request.backups,
+                    policy: request.policy,
+                    pause: request.pause,
+                    cancel: request.cancel,
+                    quality: request.quality,
+                    format: request.format,
+                    status: self.status.clone(),
+                    write_buffer_size: self.write_buffer_size,
+                    max_bytes_per_sec: self.max_bytes_per_sec,
+                    throttle: None,
+                    min_free_margin: self.min_free_margin,
+                    keep_existing_tags: self.keep_existing_tags,
+                    strict_format: self.strict_format,
+                    existing_tag: None,
+                    hosts: self.hosts.clone(),
+                    _host_permit: None,
+                    #[cfg(test)]
+                    scripted: request.scripted,
+                },
             },
             |state| async move {
                 match state {
-                    DownloadRuntimeState::Start { client, url, path } => {
-                        let file = match tokio::fs::File::create(&path).await {
-                            Ok(file) => file,
-                            Err(e) => {
-                                return Some((
-                                    DownloadEvent::Failed(AppError::Io(format!(
-                                        "Failed to create file: {}",
-                                        e
-                                    ))),
-                                    DownloadRuntimeState::Finished,
-                                ));
-                            }
-                        };
+                    DownloadRuntimeState::Start { mut ctx } => {
+                        ctx._host_permit = Some(ctx.hosts.acquire(&ctx.url).await);
+                        if ctx.keep_existing_tags {
+                            let path = ctx.path.clone();
+                            ctx.existing_tag =
+                                tokio::task::spawn_blocking(move || read_existing_tag(&path))
+                                    .await
+                                    .ok()
+                                    .flatten()
+                                    .map(Box::new);
+                        }
 
-                        match client.download_file_stream(&url).await {
-                            Ok((total_size, stream)) => Some((
-                                DownloadEvent::Progress(0.0),
-                                DownloadRuntimeState::Downloading {
-                                    file,
-                                    stream: stream.boxed(),
-                                    downloaded: 0,
-                                    total: total_size,
-                                    path,
-                                },
-                            )),
-                            Err(e) => Some((
-                                DownloadEvent::Failed(AppError::Api(e.to_string())),
+                        if ctx.cancel.as_ref().is_some_and(|c| c.is_cancelled()) {
+                            return Some((
+                                DownloadEvent::Cancelled,
                                 DownloadRuntimeState::Finished,
-                            )),
+                            ));
+                        }
+
+                        match open_download(&mut ctx).await {
+                            Ok((file, downloaded, total, stream)) => {
+                                ctx.throttle = ctx
+                                    .max_bytes_per_sec
+                                    .map(|limit| Throttle::new(limit, downloaded));
+                                Some((
+                                    progress_event(downloaded, total),
+                                    DownloadRuntimeState::Downloading {
+                                        ctx,
+                                        file,
+                                        stream,
+                                        downloaded,
+                                        total,
+                                        reconnects: 0,
+                                        checkpoint: Instant::now(),
+                                    },
+                                ))
+                            }
+                            Err(e) => {
+                                Some((DownloadEvent::Failed(e), DownloadRuntimeState::Finished))
+                            }
                         }
                     }
                     DownloadRuntimeState::Downloading {
+                        mut ctx,
                         mut file,
                         mut stream,
                         mut downloaded,
                         total,
-                        path,
-                    } => match stream.next().await {
-                        Some(Ok(chunk)) => {
-                            if let Err(e) = file.write_all(&chunk).await {
+                        mut reconnects,
+                        mut checkpoint,
+                    } => {
+                        let next = tokio::select! {
+                            biased;
+                            _ = requested(&ctx.cancel) => Err(Interrupt::Cancel),
+                            _ = requested(&ctx.pause) => Err(Interrupt::Pause),
+                            next = stream.next() => Ok(next),
+                        };
+                        let next = match next {
+                            Ok(next) => next,
+                            Err(Interrupt::Pause) => {
+                                // Same record as after a failure, so a restart continues from here
+                                let _ = file.finish().await;
+                                let record = ctx.resume_record(downloaded, total);
+                                let _ = record.save().await;
                                 return Some((
-                                    DownloadEvent::Failed(AppError::Io(format!(
-                                        "Write error: {}",
-                                        e
-                                    ))),
+                                    DownloadEvent::Paused(record),
                                     DownloadRuntimeState::Finished,
                                 ));
                             }
-
-                            downloaded += chunk.len() as u64;
-
-                            let progress = if let Some(total_size) = total {
-                                if total_size > 0 {
-                                    downloaded as f32 / total_size as f32
-                                } else {
-                                    0.0
+                            Err(Interrupt::Cancel) => {
+                                drop(file);
+                                for index in 0..=ctx.backups.len() {
+                                    let _ = tokio::fs::remove_file(ctx.partial(index)).await;
                                 }
-                            } else {
-                                0.0
-                            };
-
-                            Some((
-                                DownloadEvent::Progress(progress),
-                                DownloadRuntimeState::Downloading {
-                                    file,
-                                    stream,
-                                    downloaded,
-                                    total,
-                                    path,
-                                },
-                            ))
-                        }
-                        Some(Err(e)) => Some((
-                            DownloadEvent::Failed(AppError::Api(e.to_string())),
-                            DownloadRuntimeState::Finished,
-                        )),
-                        None => {
-                            if let Err(e) = file.sync_all().await {
+                                ResumeRecord::remove(&ctx.path).await;
                                 return Some((
-                                    DownloadEvent::Failed(AppError::Io(format!(
-                                        "Failed to sync file: {}",
-                                        e
-                                    ))),
+                                    DownloadEvent::Cancelled,
                                     DownloadRuntimeState::Finished,
                                 ));
                             }
+                        };
 
-                            Some((
-                                DownloadEvent::Completed(path),
-                                DownloadRuntimeState::Finished,
-                            ))
+                        match next {
+                            Some(Ok(chunk)) => {
+                                if let Err(TeeError { index, error }) = file.write(&chunk).await {
+                                    let target = ctx.target(index);
+                                    let error =
+                                        output_error(&ctx.partial(index), "Write error", error);
+                                    discard_partial(target, &error).await;
+                                    return Some((
+                                        DownloadEvent::Failed(error),
+                                        DownloadRuntimeState::Finished,
+                                    ));
+                                }
+
+                                downloaded += chunk.len() as u64;
+                                if let Some(throttle) = &ctx.throttle {
+                                    // Cut short by Pause/Cancel, which the next chunk turn handles
+                                    tokio::select! {
+                                        _ = throttle.wait(downloaded) => {}
+                                        _ = requested(&ctx.cancel) => {}
+                                        _ = requested(&ctx.pause) => {}
+                                    }
+                                }
+                                if checkpoint.elapsed() >= CHECKPOINT_INTERVAL {
+                                    // Best effort: a missed checkpoint only costs a longer restart
+                                    let _ = ctx.resume_record(downloaded, total).save().await;
+                                    checkpoint = Instant::now();
+                                }
+
+                                Some((
+                                    progress_event(downloaded, total),
+                                    DownloadRuntimeState::Downloading {
+                                        ctx,
+                                        file,
+                                        stream,
+                                        downloaded,
+                                        total,
+                                        reconnects,
+                                        checkpoint,
+                                    },
+                                ))
+                            }
+                            Some(Err(e)) => {
+                                // Resuming from a server that ignores Range would corrupt the file
+                                let resumable =
+                                    ctx.client.supports_ranges(&ctx.url).await.unwrap_or(false);
+
+                                // Reopen the stream from where it broke off, keeping what's written
+                                let mut last_error = e;
+                                let max_reconnects = ctx.client.download_retries();
+                                while resumable && reconnects < max_reconnects {
+                                    reconnects += 1;
+                                    ctx.status.report(&format!(
+                                        "Connection interrupted, reconnecting ({}/{})...",
+                                        reconnects, max_reconnects
+                                    ));
+                                    tokio::time::sleep(RECONNECT_DELAY * reconnects).await;
+
+                                    match ctx
+                                        .client
+                                        .download_file_stream(&ctx.url, downloaded)
+                                        .await
+                                    {
+                                        Ok((_, new_stream)) => {
+                                            stream = new_stream.boxed();
+                                            return Some((
+                                                progress_event(downloaded, total),
+                                                DownloadRuntimeState::Downloading {
+                                                    ctx,
+                                                    file,
+                                                    stream,
+                                                    downloaded,
+                                                    total,
+                                                    reconnects,
+                                                    checkpoint,
+                                                },
+                                            ));
+                                        }
+                                        Err(e) => last_error = e,
+                                    }
+                                }
+
+                                // Leave a record so a later attempt can continue from here
+                                let _ = file.finish().await;
+                                let _ = ctx.resume_record(downloaded, total).save().await;
+
+                                Some((
+                                    DownloadEvent::Failed(api_error(last_error)),
+                                    DownloadRuntimeState::Finished,
+                                ))
+                            }
+                            None => {
+                                // On Unix, writes to a moved or deleted file keep "succeeding", so
+                                // also make sure the file is still where it's supposed to be
+                                let mut finished = file.finish().await;
+                                for index in file.live() {
+                                    if finished.is_ok() && !ctx.partial(index).exists() {
+                                        finished =
+                                            file.fail(index, std::io::ErrorKind::NotFound.into());
+                                    }
+                                }
+                                if let Err(TeeError { index, error }) = finished {
+                                    let target = ctx.target(index);
+                                    let error = output_error(
+                                        &ctx.partial(index),
+                                        "Failed to sync file",
+                                        error,
+                                    );
+                                    discard_partial(target, &error).await;
+                                    return Some((
+                                        DownloadEvent::Failed(error),
+                                        DownloadRuntimeState::Finished,
+                                    ));
+                                }
+
+                                // A stream that ends early without an error would otherwise
+                                // pass for a complete file
+                                if let Some(expected) = total.filter(|&total| total != downloaded) {
+                                    for index in 0..=ctx.backups.len() {
+                                        let _ = tokio::fs::remove_file(ctx.partial(index)).await;
+                                    }
+                                    ResumeRecord::remove(&ctx.path).await;
+                                    return Some((
+                                        DownloadEvent::Failed(AppError::IncompleteDownload {
+                                            expected,
+                                            got: downloaded,
+                                        }),
+                                        DownloadRuntimeState::Finished,
+                                    ));
+                                }
+
+                                // The first copy that made it, normally the primary path
+                                let saved = ctx.target(file.live()[0]).to_path_buf();
+
+                                // Checked before carrying tags over, which changes the length
+                                match tokio::fs::metadata(ctx.partial(file.live()[0])).await {
+                                    Ok(meta) if meta.len() == downloaded => {}
+                                    Ok(meta) => {
+                                        let error = AppError::Io(format!(
+                                            "{} is {} bytes, but {} were downloaded",
+                                            saved.display(),
+                                            meta.len(),
+                                            downloaded
+                                        ));
+                                        discard_partial(&saved, &error).await;
+                                        return Some((
+                                            DownloadEvent::Failed(error),
+                                            DownloadRuntimeState::Finished,
+                                        ));
+                                    }
+                                    Err(e) => {
+                                        let error = output_error(&saved, "Failed to check file", e);
+                                        return Some((
+                                            DownloadEvent::Failed(error),
+                                            DownloadRuntimeState::Finished,
+                                        ));
+                                    }
+                                }
+
+                                ResumeRecord::remove(&ctx.path).await;
+
+                                // Only complete files ever appear under the final names
+                                for index in file.live() {
+                                    let target = ctx.target(index);
+                                    if let Err(e) =
+                                        tokio::fs::rename(ctx.partial(index), target).await
+                                    {
+                                        let error = output_error(
+                                            target,
+                                            "Failed to move file into place",
+                                            e,
+                                        );
+                                        return Some((
+                                            DownloadEvent::Failed(error),
+                                            DownloadRuntimeState::Finished,
+                                        ));
+                                    }
+                                }
+
+                                let live: Vec<PathBuf> = file
+                                    .live()
+                                    .into_iter()
+                                    .map(|index| ctx.target(index).to_path_buf())
+                                    .collect();
+                                let saved = match settle_format(&ctx, live).await {
+                                    Ok(saved) => saved,
+                                    Err(error) => {
+                                        return Some((
+                                            DownloadEvent::Failed(error),
+                                            DownloadRuntimeState::Finished,
+                                        ))
+                                    }
+                                };
+
+                                // ID3 tags only belong in the format that was asked for
+                                let kept_format = saved == ctx.path || ctx.backups.contains(&saved);
+                                if let Some(tag) = ctx.existing_tag.take().filter(|_| kept_format) {
+                                    let path = saved.clone();
+                                    let applied =
+                                        tokio::task::spawn_blocking(move || apply_tag(&path, &tag))
+                                            .await;
+                                    // The audio itself is fine, so only mention it
+                                    if !matches!(applied, Ok(Ok(()))) {
+                                        ctx.status.report("Could not carry over the previous tags");
+                                    }
+                                }
+
+                                Some((
+                                    DownloadEvent::Completed {
+                                        path: saved,
+                                        size: downloaded,
+                                        quality: ctx.quality,
+                                        format: ctx.format,
+                                    },
+                                    DownloadRuntimeState::Finished,
+                                ))
+                            }
                         }
-                    },
+                    }
                     DownloadRuntimeState::Finished => None,
                 }
             },
@@ -165,18 +846,1504 @@ impl DownloadCoordinator {
     }
 }
 
-enum DownloadRuntimeState {
+/// Open the output file and the download stream, continuing an interrupted earlier
+/// run when a resume record for the same URL is found next to the file
+async fn open_download(
+    ctx: &mut DownloadContext,
+) -> Result<(Tee<OutputWriter>, u64, Option<u64>, ChunkStream), AppError> {
+    let tuning = WriteTuning::for_path(&ctx.path, ctx.write_buffer_size);
+
+    // Backup copies would be missing the part written by the earlier run
+    let offset = if ctx.backups.is_empty() {
+        ResumeRecord::resume_offset(&ctx.path, &ctx.url).await
+    } else {
+        0
+    };
+    if offset > 0 {
+        ctx.status.report(&format!(
+            "Resuming interrupted download at {}...",
+            format_bytes(offset)
+        ));
+        if let Ok((total, stream)) = connect(ctx, offset).await {
+            check_free_space(
+                &ctx.path,
+                total.map(|total| total.saturating_sub(offset)),
+                ctx.min_free_margin,
+            )?;
+            let partial = ResumeRecord::partial_path(&ctx.path);
+            let file = OutputWriter::resume(&partial, offset, tuning).await?;
+            return Ok((Tee::new(vec![file], ctx.policy), offset, total, stream));
+        }
+    }
+
+    ctx.status.report("Connecting to download server...");
+
+    let (total, stream) = connect(ctx, 0).await.map_err(api_error)?;
+
+    // Checked before creating the files, so a full disk doesn't leave empty ones behind
+    let targets: Vec<&Path> = std::iter::once(ctx.path.as_path())
+        .chain(ctx.backups.iter().map(PathBuf::as_path))
+        .collect();
+    for target in &targets {
+        check_free_space(target, total, ctx.min_free_margin)?;
+    }
+
+    let mut files = Vec::with_capacity(targets.len());
+    for target in targets {
+        let tuning = WriteTuning::for_path(target, ctx.write_buffer_size);
+        let partial = ResumeRecord::partial_path(target);
+        files.push(OutputWriter::create(&partial, tuning).await?);
+    }
+    Ok((Tee::new(files, ctx.policy), 0, total, stream))
+}
+
+/// Request the download from `offset`, switching to a freshly resolved link once if
+/// the current one has expired (typical for a download paused in an earlier session)
+async fn connect(
+    ctx: &mut DownloadContext,
+    offset: u64,
+) -> crate::api::Result<(Option<u64>, ChunkStream)> {
+    #[cfg(test)]
+    if let Some(response) = ctx.scripted.take() {
+        let chunks = response.chunks.into_iter().map(Ok);
+        return Ok((response.total, futures::stream::iter(chunks).boxed()));
+    }
+
+    match ctx.client.download_file_stream(&ctx.url, offset).await {
+        Ok((total, stream)) => return Ok((total, stream.boxed())),
+        Err(ApiError::LinkExpired) if ctx.source.is_some() => {}
+        Err(e) => return Err(e),
+    }
+
+    let video_id = ctx
+        .source
+        .as_deref()
+        .and_then(extract_video_id)
+        .ok_or(ApiError::LinkExpired)?;
+    ctx.status
+        .report("Download link expired, fetching a fresh one...");
+    let (_title, url) = ctx
+        .client
+        .get_download_info(&video_id, convert_options(ctx.quality, ctx.format))
+        .await?;
+    ctx.url = url;
+
+    let (total, stream) = ctx.client.download_file_stream(&ctx.url, offset).await?;
+    Ok((total, stream.boxed()))
+}
+
+/// Why a running download stopped reading its stream
+enum Interrupt {
+    Pause,
+    Cancel,
+}
+
+/// Resolves once `token` is cancelled; never without one
+async fn requested(token: &Option<CancellationToken>) {
+    match token {
+        Some(token) => token.cancelled().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Compare what arrived in `live` (primary copy first) with the requested format. A
+/// mismatch fails and deletes the files in strict mode; otherwise they're renamed to the
+/// extension of what was delivered. Returns where the primary copy ended up
+async fn settle_format(ctx: &DownloadContext, live: Vec<PathBuf>) -> Result<PathBuf, AppError> {
+    let expected = ctx.format.extension();
+    let delivered = match sniff_extension(&live[0]).await {
+        Some(delivered) if delivered != expected => delivered,
+        _ => return Ok(live[0].clone()),
+    };
+
+    if ctx.strict_format {
+        for path in &live {
+            let _ = tokio::fs::remove_file(path).await;
+        }
+        return Err(AppError::InvalidContent(format!(
+            "expected {}, but the server sent {}",
+            expected, delivered
+        )));
+    }
+
+    let mut renamed = Vec::with_capacity(live.len());
+    for path in live {
+        // An earlier download may already be there under the delivered extension
+        let target = resolve_unique_path(&path.with_extension(delivered));
+        tokio::fs::rename(&path, &target)
+            .await
+            .map_err(|e| output_error(&path, "Failed to rename file", e))?;
+        renamed.push(target);
+    }
+    ctx.status.report(&format!(
+        "Server sent {} instead of {}; kept it as is",
+        delivered, expected
+    ));
+    Ok(renamed.swap_remove(0))
+}
+
+/// Extension of the audio container `path` starts with, if it's one we recognise
+async fn sniff_extension(path: &Path) -> Option<&'static str> {
+    use tokio::io::AsyncReadExt;
+
+    let mut header = Vec::with_capacity(12);
+    let file = tokio::fs::File::open(path).await.ok()?;
+    file.take(12).read_to_end(&mut header).await.ok()?;
+    container_extension(&header)
+}
+
+fn container_extension(header: &[u8]) -> Option<&'static str> {
+    match header {
+        [b'I', b'D', b'3', ..] => Some("mp3"),
+        // MPEG audio frame sync with a non-zero layer (layer 0 would be AAC/ADTS)
+        [0xFF, second, ..] if second & 0xE0 == 0xE0 && second & 0x06 != 0 => Some("mp3"),
+        [_, _, _, _, b'f', b't', b'y', b'p', ..] => Some("m4a"),
+        [0x1A, 0x45, 0xDF, 0xA3, ..] => Some("webm"),
+        // Ogg from these backends is always Opus
+        [b'O', b'g', b'g', b'S', ..] => Some("opus"),
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'A', b'V', b'E', ..] => Some("wav"),
+        _ => None,
+    }
+}
+
+/// Clean up the partial file of a download into `path` after the output location went
+/// away; other write errors keep it and its resume record for a later attempt
+async fn discard_partial(path: &Path, error: &AppError) {
+    if matches!(error, AppError::Io(message) if message == OUTPUT_DISAPPEARED) {
+        let _ = tokio::fs::remove_file(ResumeRecord::partial_path(path)).await;
+        ResumeRecord::remove(path).await;
+    }
+}
+
+fn build_plan(
+    title: String,
+    download_url: String,
+    video_id: &str,
+    naming: &FilenameTemplate,
+) -> DownloadPlan {
+    let suggested_filename = naming.render(&title, video_id);
+
+    DownloadPlan {
+        title,
+        download_url,
+        suggested_filename,
+        album: None,
+        quality: naming.quality(),
+        format: naming.format(),
+    }
+}
+
+/// Await `future`, giving up with `None` once the optional timeout expires
+async fn with_timeout<T>(
+    timeout: Option<Duration>,
+    future: impl std::future::Future<Output = Option<T>>,
+) -> Option<T> {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, future).await.ok().flatten(),
+        None => future.await,
+    }
+}
+
+/// Progress against the size when it's known, otherwise just the byte count (chunked responses)
+fn progress_event(downloaded: u64, total: Option<u64>) -> DownloadEvent {
+    match total {
+        Some(total) if total > 0 => DownloadEvent::Progress { downloaded, total },
+        _ => DownloadEvent::Received(downloaded),
+    }
+}
+
+enum PrepareState {
     Start {
         client: ApiClient,
-        url: String,
-        path: PathBuf,
+        youtube_url: String,
+    },
+    Init {
+        client: ApiClient,
+        video_id: String,
+        /// Set once init has been re-run after an expired signature
+        resigned: bool,
+    },
+    Converting {
+        client: ApiClient,
+        video_id: String,
+        convert_url: String,
+        resigned: bool,
+    },
+    Finished,
+}
+
+/// Callback receiving intermediate status lines from the coordinator
+pub type StatusHook = Arc<dyn Fn(&str) + Send + Sync>;
+
+/// Routes status lines to the hook unless quiet mode is on
+#[derive(Clone, Default)]
+struct StatusReporter {
+    hook: Option<StatusHook>,
+    quiet: bool,
+}
+
+impl StatusReporter {
+    fn report(&self, message: &str) {
+        if self.quiet {
+            return;
+        }
+        if let Some(hook) = &self.hook {
+            hook(message);
+        }
+    }
+}
+
+/// What to ask the convert endpoint for to end up with `quality` and `format`
+fn convert_options(quality: AudioQuality, format: OutputFormat) -> ConvertOptions {
+    let format = match format {
+        OutputFormat::Mp3 => AudioFormat::Mp3,
+        OutputFormat::M4a => AudioFormat::M4a,
+        OutputFormat::Opus => AudioFormat::Opus,
+        OutputFormat::Wav => AudioFormat::Wav,
+    };
+    let bitrate = match quality {
+        AudioQuality::Source => None,
+        AudioQuality::Kbps(kbps) => Some(kbps),
+    };
+    ConvertOptions { format, bitrate }
+}
+
+/// App-level error for a backend failure, keeping connection failures recognizable
+fn api_error(error: ApiError) -> AppError {
+    if error.is_connection_failure() {
+        AppError::Network(error.to_string())
+    } else {
+        AppError::Api(error.to_string())
+    }
+}
+
+/// Everything a single download needs that doesn't change while it runs
+struct DownloadContext {
+    client: ApiClient,
+    url: String,
+    path: PathBuf,
+    source: Option<String>,
+    /// Extra copies written alongside `path`
+    backups: Vec<PathBuf>,
+    policy: TeePolicy,
+    pause: Option<CancellationToken>,
+    cancel: Option<CancellationToken>,
+    /// Requested output, reported back on completion
+    quality: AudioQuality,
+    format: OutputFormat,
+    status: StatusReporter,
+    write_buffer_size: Option<usize>,
+    max_bytes_per_sec: Option<u64>,
+    /// Pacing of the running download, when capped
+    throttle: Option<Throttle>,
+    min_free_margin: u64,
+    keep_existing_tags: bool,
+    strict_format: bool,
+    /// Tag of the file that was at `path` before the download replaced it
+    existing_tag: Option<Box<id3::Tag>>,
+    hosts: HostLimiter,
+    /// Slot on the download host, released when the download ends either way
+    _host_permit: Option<OwnedSemaphorePermit>,
+    #[cfg(test)]
+    scripted: Option<ScriptedResponse>,
+}
+
+/// Holds a download's average speed under a cap by pausing after chunks that arrive early
+#[derive(Debug, Clone, Copy)]
+struct Throttle {
+    bytes_per_sec: u64,
+    started: tokio::time::Instant,
+    /// Bytes already on disk when the clock started, e.g. from an earlier run
+    from: u64,
+}
+
+impl Throttle {
+    fn new(bytes_per_sec: u64, from: u64) -> Self {
+        Self {
+            bytes_per_sec,
+            started: tokio::time::Instant::now(),
+            from,
+        }
+    }
+
+    /// Wait until having `downloaded` bytes no longer puts the download ahead of the cap
+    async fn wait(&self, downloaded: u64) {
+        let due = Duration::from_secs_f64(
+            downloaded.saturating_sub(self.from) as f64 / self.bytes_per_sec as f64,
+        );
+        tokio::time::sleep_until(self.started + due).await;
+    }
+}
+
+/// Canned download response with caller-chosen chunk boundaries, used in place of the
+/// HTTP stream so tests can exercise the chunk loop deterministically
+#[cfg(test)]
+#[derive(Debug, Clone)]
+struct ScriptedResponse {
+    total: Option<u64>,
+    chunks: Vec<bytes::Bytes>,
+}
+
+impl DownloadContext {
+    /// Output path behind tee index `index`: the primary path first, then the backups
+    fn target(&self, index: usize) -> &Path {
+        match index {
+            0 => &self.path,
+            _ => &self.backups[index - 1],
+        }
+    }
+
+    /// File the copy at `target(index)` is written to until it's complete
+    fn partial(&self, index: usize) -> PathBuf {
+        ResumeRecord::partial_path(self.target(index))
+    }
+
+    fn resume_record(&self, downloaded_bytes: u64, total: Option<u64>) -> ResumeRecord {
+        ResumeRecord {
+            url: self.url.clone(),
+            source: self.source.clone(),
+            path: self.path.clone(),
+            downloaded_bytes,
+            total,
+        }
+    }
+}
+
+type ChunkStream = BoxStream<'static, crate::api::Result<bytes::Bytes>>;
+
+enum DownloadRuntimeState {
+    Start {
+        ctx: DownloadContext,
     },
     Downloading {
-        file: tokio::fs::File,
-        stream: BoxStream<'static, crate::api::Result<bytes::Bytes>>,
+        ctx: DownloadContext,
+        file: Tee<OutputWriter>,
+        stream: ChunkStream,
         downloaded: u64,
         total: Option<u64>,
-        path: PathBuf,
+        reconnects: u32,
+        /// When the resume record was last written
+        checkpoint: Instant,
     },
     Finished,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::models::ApiConfig;
+    use crate::application::test_support::{mock_backend, AUTH_PAGE};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    async fn count_status_updates(quiet: bool) -> usize {
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock("GET", "/file.mp3")
+            .with_body("0123456789")
+            .create_async()
+            .await;
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let coordinator = DownloadCoordinator::new(ApiClient::new(Default::default()))
+            .with_status_hook(move |_| {
+                counter.fetch_add(1, Ordering::SeqCst);
+            })
+            .quiet(quiet);
+
+        let dir = tempfile::tempdir().unwrap();
+        let events: Vec<DownloadEvent> = coordinator
+            .download(DownloadRequest::new(
+                format!("{}/file.mp3", server.url()),
+                dir.path().join("song.mp3"),
+            ))
+            .collect()
+            .await;
+        assert!(matches!(
+            events.last(),
+            Some(DownloadEvent::Completed { .. })
+        ));
+
+        calls.load(Ordering::SeqCst)
+    }
+
+    #[tokio::test]
+    async fn test_download_channel_delivers_full_sequence() {
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock("GET", "/file.mp3")
+            .with_body("0123456789")
+            .create_async()
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("song.mp3");
+        let coordinator = DownloadCoordinator::new(ApiClient::new(Default::default()));
+        let mut channel = coordinator.download(DownloadRequest::new(
+            format!("{}/file.mp3", server.url()),
+            path.clone(),
+        ));
+
+        let mut events = Vec::new();
+        while let Some(event) = channel.next().await {
+            events.push(event);
+        }
+
+        let progress: Vec<(u64, u64)> = events
+            .iter()
+            .filter_map(|event| match event {
+                DownloadEvent::Progress { downloaded, total } => Some((*downloaded, *total)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(progress.first(), Some(&(0, 10)));
+        assert_eq!(progress.last(), Some(&(10, 10)));
+        assert!(progress.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+        assert_eq!(events.len(), progress.len() + 1);
+        assert!(
+            matches!(events.last(), Some(DownloadEvent::Completed { path: p, size: 10, .. }) if *p == path)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_completion_reports_bytes_written() {
+        let mut server = mockito::Server::new_async().await;
+        let body = vec![7u8; 50_000];
+        let _mock = server
+            .mock("GET", "/file.mp3")
+            .with_body(&body)
+            .create_async()
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let coordinator = DownloadCoordinator::new(ApiClient::new(Default::default()));
+        let events: Vec<DownloadEvent> = coordinator
+            .download(DownloadRequest::new(
+                format!("{}/file.mp3", server.url()),
+                dir.path().join("song.mp3"),
+            ))
+            .collect()
+            .await;
+
+        let Some(DownloadEvent::Completed { path, size, .. }) = events.last() else {
+            panic!("download did not complete: {:?}", events.last());
+        };
+        assert_eq!(*size, body.len() as u64);
+        assert_eq!(std::fs::metadata(path).unwrap().len(), *size);
+    }
+
+    async fn scripted_download(
+        total: Option<u64>,
+        chunks: Vec<&'static [u8]>,
+    ) -> Vec<DownloadEvent> {
+        let dir = tempfile::tempdir().unwrap();
+        let request = DownloadRequest::new(
+            "https://cdn.example.com/file.mp3".into(),
+            dir.path().join("song.mp3"),
+        )
+        .scripted(total, chunks);
+        DownloadCoordinator::new(ApiClient::new(Default::default()))
+            .download(request)
+            .collect()
+            .await
+    }
+
+    /// Byte counts carried by the progress events, in order, starting with the one sent
+    /// on connecting
+    fn progress_of(events: &[DownloadEvent]) -> Vec<(u64, Option<u64>)> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                DownloadEvent::Progress { downloaded, total } => Some((*downloaded, Some(*total))),
+                DownloadEvent::Received(downloaded) => Some((*downloaded, None)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn completed_size(events: &[DownloadEvent]) -> u64 {
+        match events.last() {
+            Some(DownloadEvent::Completed { size, .. }) => *size,
+            other => panic!("download did not complete: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_single_byte_chunks_advance_one_byte_at_a_time() {
+        let events = scripted_download(Some(4), vec![b"a", b"b", b"c", b"d"]).await;
+
+        assert_eq!(
+            progress_of(&events),
+            vec![
+                (0, Some(4)),
+                (1, Some(4)),
+                (2, Some(4)),
+                (3, Some(4)),
+                (4, Some(4))
+            ]
+        );
+        assert_eq!(completed_size(&events), 4);
+    }
+
+    #[tokio::test]
+    async fn test_one_huge_chunk_completes_in_one_step() {
+        static BODY: [u8; 1 << 20] = [7; 1 << 20];
+        let size = BODY.len() as u64;
+        let events = scripted_download(Some(size), vec![&BODY]).await;
+
+        assert_eq!(
+            progress_of(&events),
+            vec![(0, Some(size)), (size, Some(size))]
+        );
+        assert_eq!(completed_size(&events), size);
+    }
+
+    #[tokio::test]
+    async fn test_empty_chunks_leave_byte_count_unchanged() {
+        let events = scripted_download(Some(3), vec![b"", b"ab", b"", b"", b"c"]).await;
+
+        assert_eq!(
+            progress_of(&events),
+            vec![
+                (0, Some(3)),
+                (0, Some(3)),
+                (2, Some(3)),
+                (2, Some(3)),
+                (2, Some(3)),
+                (3, Some(3))
+            ]
+        );
+        assert_eq!(completed_size(&events), 3);
+    }
+
+    #[tokio::test]
+    async fn test_short_body_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("song.mp3");
+        let request = DownloadRequest::new("https://cdn.example.com/file.mp3".into(), path.clone())
+            .scripted(Some(10), vec![b"01234"]);
+        let events: Vec<DownloadEvent> =
+            DownloadCoordinator::new(ApiClient::new(Default::default()))
+                .download(request)
+                .collect()
+                .await;
+
+        assert!(matches!(
+            events.last(),
+            Some(DownloadEvent::Failed(AppError::IncompleteDownload {
+                expected: 10,
+                got: 5
+            }))
+        ));
+        assert!(!path.exists());
+        assert!(!ResumeRecord::partial_path(&path).exists());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rate_limit_spreads_chunks_over_time() {
+        let dir = tempfile::tempdir().unwrap();
+        let request = DownloadRequest::new(
+            "https://cdn.example.com/file.mp3".into(),
+            dir.path().join("song.mp3"),
+        )
+        .scripted(Some(1000), vec![&[7; 100]; 10]);
+        let started = tokio::time::Instant::now();
+        let events: Vec<DownloadEvent> =
+            DownloadCoordinator::new(ApiClient::new(Default::default()))
+                .max_bytes_per_sec(Some(200))
+                .download(request)
+                .collect()
+                .await;
+
+        assert!(started.elapsed() >= Duration::from_secs(5));
+        assert_eq!(progress_of(&events).len(), 11);
+        assert_eq!(completed_size(&events), 1000);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_cuts_throttle_wait_short() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("song.mp3");
+        let cancel = CancellationToken::new();
+        let request = DownloadRequest::new("https://cdn.example.com/file.mp3".into(), path.clone())
+            .scripted(Some(200), vec![&[7; 100]; 2])
+            .cancel_on(cancel.clone());
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            cancel.cancel();
+        });
+
+        // The first chunk alone is due ten seconds in at this cap
+        let started = Instant::now();
+        let events: Vec<DownloadEvent> =
+            DownloadCoordinator::new(ApiClient::new(Default::default()))
+                .max_bytes_per_sec(Some(10))
+                .download(request)
+                .collect()
+                .await;
+
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert!(matches!(events.last(), Some(DownloadEvent::Cancelled)));
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_unknown_total_reports_received_bytes() {
+        let events = scripted_download(None, vec![b"ab", b"cde"]).await;
+
+        assert_eq!(progress_of(&events), vec![(0, None), (2, None), (5, None)]);
+        assert_eq!(completed_size(&events), 5);
+    }
+
+    #[test]
+    fn test_container_extension_from_header() {
+        assert_eq!(container_extension(b"ID3\x04\0\0"), Some("mp3"));
+        assert_eq!(container_extension(&[0xFF, 0xFB, 0x90, 0x64]), Some("mp3"));
+        assert_eq!(container_extension(&[0xFF, 0xF1, 0x50, 0x80]), None);
+        assert_eq!(container_extension(b"\0\0\0\x20ftypM4A "), Some("m4a"));
+        assert_eq!(container_extension(b"OggS\0\x02"), Some("opus"));
+        assert_eq!(container_extension(b"RIFF\x24\0\0\0WAVEfmt "), Some("wav"));
+        assert_eq!(container_extension(&[7; 12]), None);
+    }
+
+    async fn download_mismatched_format(strict: bool) -> (tempfile::TempDir, DownloadEvent) {
+        download_mismatched_format_into(tempfile::tempdir().unwrap(), strict).await
+    }
+
+    async fn download_mismatched_format_into(
+        dir: tempfile::TempDir,
+        strict: bool,
+    ) -> (tempfile::TempDir, DownloadEvent) {
+        let request = DownloadRequest::new(
+            "https://cdn.example.com/file.mp3".into(),
+            dir.path().join("song.mp3"),
+        )
+        .scripted(Some(8), vec![b"OggS", b"\0\x02\0\0"]);
+        let events: Vec<DownloadEvent> =
+            DownloadCoordinator::new(ApiClient::new(Default::default()))
+                .strict_format(strict)
+                .download(request)
+                .collect()
+                .await;
+        (dir, events.last().unwrap().clone())
+    }
+
+    #[tokio::test]
+    async fn test_strict_format_rejects_mismatch() {
+        let (dir, last) = download_mismatched_format(true).await;
+
+        assert!(
+            matches!(&last, DownloadEvent::Failed(AppError::InvalidContent(message)) if message.contains("opus")),
+            "{:?}",
+            last
+        );
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_best_effort_format_keeps_delivered_extension() {
+        let (dir, last) = download_mismatched_format(false).await;
+
+        let DownloadEvent::Completed { path, size, .. } = last else {
+            panic!("download did not complete: {:?}", last);
+        };
+        assert_eq!(path, dir.path().join("song.opus"));
+        assert_eq!(size, 8);
+        assert!(path.exists());
+        assert!(!dir.path().join("song.mp3").exists());
+    }
+
+    #[tokio::test]
+    async fn test_delivered_extension_never_replaces_existing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("song.opus"), b"earlier").unwrap();
+        let (dir, last) = download_mismatched_format_into(dir, false).await;
+
+        let DownloadEvent::Completed { path, .. } = last else {
+            panic!("download did not complete: {:?}", last);
+        };
+        assert_eq!(path, dir.path().join("song (1).opus"));
+        assert_eq!(
+            std::fs::read(dir.path().join("song.opus")).unwrap(),
+            b"earlier"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_quiet_mode_never_invokes_status_hook() {
+        assert!(count_status_updates(false).await > 0);
+        assert_eq!(count_status_updates(true).await, 0);
+    }
+
+    #[test]
+    fn test_auto_save_path_uses_existing_output_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let coordinator = DownloadCoordinator::new(ApiClient::new(Default::default()));
+        assert_eq!(coordinator.auto_save_path("Song.mp3"), None);
+
+        let coordinator = coordinator.output_dir(Some(dir.path().to_path_buf()));
+        assert_eq!(
+            coordinator.auto_save_path("Song.mp3"),
+            Some(dir.path().join("Song.mp3"))
+        );
+
+        std::fs::write(dir.path().join("Song.mp3"), b"earlier").unwrap();
+        assert_eq!(
+            coordinator.auto_save_path("Song.mp3"),
+            Some(dir.path().join("Song (1).mp3"))
+        );
+
+        // A folder that's gone means asking again
+        let coordinator = coordinator.output_dir(Some(dir.path().join("missing")));
+        assert_eq!(coordinator.auto_save_path("Song.mp3"), None);
+    }
+
+    #[tokio::test]
+    async fn test_prepare_download_requests_and_names_chosen_format() {
+        let mut server = mockito::Server::new_async().await;
+        let convert = server
+            .mock("GET", "/convert")
+            .match_query(mockito::Matcher::UrlEncoded("f".into(), "m4a".into()))
+            .with_body(
+                r#"{"error":0,"progressURL":"","downloadURL":"https://cdn.example.com/file.m4a","redirectURL":"","title":"Artist - Song"}"#,
+            )
+            .expect(1)
+            .create_async()
+            .await;
+        let config = mock_backend(&mut server).await;
+
+        let coordinator = DownloadCoordinator::new(ApiClient::new(config)).filename_template(
+            FilenameTemplate::new(None, AudioQuality::Source, OutputFormat::M4a),
+        );
+        let plan = coordinator
+            .prepare_download("https://youtu.be/z0vCwGUZe1I".to_string())
+            .await
+            .unwrap();
+
+        assert_eq!(plan.suggested_filename, "Artist - Song.m4a");
+        assert_eq!(plan.format, OutputFormat::M4a);
+        convert.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_prepare_stream_reports_each_stage() {
+        let mut server = mockito::Server::new_async().await;
+        let config = mock_backend(&mut server).await;
+
+        let coordinator = DownloadCoordinator::new(ApiClient::new(config));
+        let events: Vec<DownloadEvent> = coordinator
+            .prepare_stream("https://youtu.be/z0vCwGUZe1I".to_string())
+            .collect()
+            .await;
+
+        assert!(matches!(
+            events.as_slice(),
+            [
+                DownloadEvent::Preparing(PrepareStage::Init),
+                DownloadEvent::Preparing(PrepareStage::Converting),
+                DownloadEvent::Preparing(PrepareStage::Resolved(plan)),
+            ] if plan.title == "Artist - Song"
+                && plan.suggested_filename == "Artist - Song.mp3"
+                && plan.download_url == "https://cdn.example.com/file.mp3"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_prepare_stream_reinits_after_signature_expiry() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/")
+            .with_body(AUTH_PAGE)
+            .expect(2)
+            .create_async()
+            .await;
+        for sig in ["stale", "fresh"] {
+            server
+                .mock("GET", "/init")
+                .match_query(mockito::Matcher::Any)
+                .with_body(format!(
+                    r#"{{"convertURL":"{}/convert?sig={}","error":"0"}}"#,
+                    server.url(),
+                    sig
+                ))
+                .expect(1)
+                .create_async()
+                .await;
+        }
+        let expired = server
+            .mock("GET", "/convert")
+            .match_query(mockito::Matcher::UrlEncoded("sig".into(), "stale".into()))
+            .with_body(
+                r#"{"error":4,"progressURL":"","downloadURL":"","redirectURL":"","message":"Signature expired"}"#,
+            )
+            .create_async()
+            .await;
+        let fresh = server
+            .mock("GET", "/convert")
+            .match_query(mockito::Matcher::UrlEncoded("sig".into(), "fresh".into()))
+            .with_body(
+                r#"{"error":0,"progressURL":"","downloadURL":"https://cdn.example.com/file.mp3","redirectURL":"","title":"Artist - Song"}"#,
+            )
+            .create_async()
+            .await;
+
+        let coordinator = DownloadCoordinator::new(ApiClient::new(ApiConfig {
+            auth_page_url: format!("{}/", server.url()),
+            base_init_url: server.url(),
+            ..Default::default()
+        }));
+        let events: Vec<DownloadEvent> = coordinator
+            .prepare_stream("https://youtu.be/z0vCwGUZe1I".to_string())
+            .collect()
+            .await;
+
+        assert!(matches!(
+            events.as_slice(),
+            [
+                DownloadEvent::Preparing(PrepareStage::Init),
+                DownloadEvent::Preparing(PrepareStage::Converting),
+                DownloadEvent::Preparing(PrepareStage::Init),
+                DownloadEvent::Preparing(PrepareStage::Converting),
+                DownloadEvent::Preparing(PrepareStage::Resolved(plan)),
+            ] if plan.download_url == "https://cdn.example.com/file.mp3"
+        ));
+        expired.assert_async().await;
+        fresh.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_end_to_end_download_against_mock_backend() {
+        let mut server = mockito::Server::new_async().await;
+        let config = mock_backend(&mut server).await;
+        let download = server
+            .mock("GET", "/file.mp3")
+            .with_body("mp3 bytes")
+            .create_async()
+            .await;
+
+        let coordinator = DownloadCoordinator::new(ApiClient::new(config));
+        let plan = coordinator
+            .prepare_download("https://youtu.be/z0vCwGUZe1I".to_string())
+            .await
+            .unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(&plan.suggested_filename);
+        let events: Vec<DownloadEvent> = coordinator
+            .download(DownloadRequest::new(plan.download_url, path.clone()))
+            .collect()
+            .await;
+
+        assert!(
+            matches!(events.last(), Some(DownloadEvent::Completed { path: p, .. }) if *p == path)
+        );
+        assert_eq!(std::fs::read(&path).unwrap(), b"mp3 bytes");
+        download.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_download_continues_from_resume_record() {
+        let mut server = mockito::Server::new_async().await;
+        let url = format!("{}/file.mp3", server.url());
+        let mock = server
+            .mock("GET", "/file.mp3")
+            .match_header("range", "bytes=5-")
+            .with_status(206)
+            .with_body("56789")
+            .create_async()
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("song.mp3");
+        tokio::fs::write(ResumeRecord::partial_path(&path), b"01234")
+            .await
+            .unwrap();
+        ResumeRecord {
+            url: url.clone(),
+            source: None,
+            path: path.clone(),
+            downloaded_bytes: 5,
+            total: Some(10),
+        }
+        .save()
+        .await
+        .unwrap();
+
+        let coordinator = DownloadCoordinator::new(ApiClient::new(Default::default()));
+        let events: Vec<DownloadEvent> = coordinator
+            .download(DownloadRequest::new(url, path.clone()))
+            .collect()
+            .await;
+
+        assert!(matches!(
+            events.last(),
+            Some(DownloadEvent::Completed { .. })
+        ));
+        // Progress picks up at the resumed position
+        assert_eq!(progress_of(&events).first(), Some(&(5, Some(10))));
+        assert_eq!(tokio::fs::read(&path).await.unwrap(), b"0123456789");
+        assert!(!ResumeRecord::partial_path(&path).exists());
+        assert_eq!(ResumeRecord::load(&path).await, None);
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_resume_ignored_by_server_restarts_from_zero() {
+        let mut server = mockito::Server::new_async().await;
+        let url = format!("{}/file.mp3", server.url());
+        // Answers the ranged request with the whole file, then the fresh one the same way
+        let mock = server
+            .mock("GET", "/file.mp3")
+            .with_body("0123456789")
+            .expect(2)
+            .create_async()
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("song.mp3");
+        tokio::fs::write(ResumeRecord::partial_path(&path), b"01234")
+            .await
+            .unwrap();
+        ResumeRecord {
+            url: url.clone(),
+            source: None,
+            path: path.clone(),
+            downloaded_bytes: 5,
+            total: Some(10),
+        }
+        .save()
+        .await
+        .unwrap();
+
+        let coordinator = DownloadCoordinator::new(ApiClient::new(Default::default()));
+        let events: Vec<DownloadEvent> = coordinator
+            .download(DownloadRequest::new(url, path.clone()))
+            .collect()
+            .await;
+
+        assert_eq!(completed_size(&events), 10);
+        assert_eq!(progress_of(&events).first(), Some(&(0, Some(10))));
+        assert_eq!(tokio::fs::read(&path).await.unwrap(), b"0123456789");
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_completed_download_removes_stale_resume_record() {
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock("GET", "/file.mp3")
+            .with_body("0123456789")
+            .create_async()
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("song.mp3");
+        ResumeRecord {
+            url: "https://cdn.example.com/other.mp3".to_string(),
+            source: None,
+            path: path.clone(),
+            downloaded_bytes: 3,
+            total: None,
+        }
+        .save()
+        .await
+        .unwrap();
+
+        let coordinator = DownloadCoordinator::new(ApiClient::new(Default::default()));
+        let _: Vec<DownloadEvent> = coordinator
+            .download(DownloadRequest::new(
+                format!("{}/file.mp3", server.url()),
+                path.clone(),
+            ))
+            .collect()
+            .await;
+
+        assert_eq!(tokio::fs::read(&path).await.unwrap(), b"0123456789");
+        assert!(!ResumeRecord::record_path(&path).exists());
+    }
+
+    #[tokio::test]
+    async fn test_prepare_stream_rejects_invalid_url() {
+        let coordinator = DownloadCoordinator::new(ApiClient::new(Default::default()));
+        let events: Vec<DownloadEvent> = coordinator
+            .prepare_stream("not a url".to_string())
+            .collect()
+            .await;
+
+        assert!(matches!(
+            events.as_slice(),
+            [DownloadEvent::Failed(AppError::InvalidInput)]
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_chunked_download_reports_received_bytes() {
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock("GET", "/file.mp3")
+            .with_chunked_body(|w| {
+                w.write_all(b"01234")?;
+                w.write_all(b"56789")
+            })
+            .create_async()
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("song.mp3");
+        let coordinator = DownloadCoordinator::new(ApiClient::new(Default::default()));
+        let events: Vec<DownloadEvent> = coordinator
+            .download(DownloadRequest::new(
+                format!("{}/file.mp3", server.url()),
+                path.clone(),
+            ))
+            .collect()
+            .await;
+
+        let received: Vec<u64> = events
+            .iter()
+            .filter_map(|e| match e {
+                DownloadEvent::Received(bytes) => Some(*bytes),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(received.first(), Some(&0));
+        assert_eq!(received.last(), Some(&10));
+        assert!(received.windows(2).all(|w| w[0] <= w[1]));
+        assert!(matches!(
+            events.last(),
+            Some(DownloadEvent::Completed { .. })
+        ));
+        assert_eq!(std::fs::read(&path).unwrap(), b"0123456789");
+    }
+
+    #[tokio::test]
+    async fn test_with_timeout_returns_none_on_expiry() {
+        let never = futures::future::pending::<Option<u32>>();
+        assert_eq!(
+            with_timeout(Some(Duration::from_millis(10)), never).await,
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_with_timeout_passes_through_result() {
+        let ready = async { Some(7) };
+        assert_eq!(
+            with_timeout(Some(Duration::from_secs(5)), ready).await,
+            Some(7)
+        );
+        assert_eq!(with_timeout(None, async { Some(8) }).await, Some(8));
+    }
+
+    const TRUNCATED_BODY: &[u8] = b"HTTP/1.1 200 OK\r\ncontent-length: 10\r\n\r\n01234";
+
+    /// Serve one canned raw response per connection, in order
+    async fn spawn_raw_server(responses: Vec<&'static [u8]>) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            for response in responses {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = [0u8; 1024];
+                let n = socket.read(&mut request).await.unwrap();
+                let request = String::from_utf8_lossy(&request[..n]).to_lowercase();
+                if response.starts_with(b"HTTP/1.1 206") {
+                    assert!(request.contains("range: bytes=5-"));
+                }
+                socket.write_all(response).await.unwrap();
+                socket.shutdown().await.unwrap();
+            }
+        });
+
+        format!("http://{}/file.mp3", addr)
+    }
+
+    #[tokio::test]
+    async fn test_download_reconnects_after_stream_error() {
+        let url = spawn_raw_server(vec![
+            TRUNCATED_BODY,
+            b"HTTP/1.1 200 OK\r\naccept-ranges: bytes\r\ncontent-length: 10\r\n\r\n",
+            b"HTTP/1.1 206 Partial Content\r\ncontent-length: 5\r\n\r\n56789",
+        ])
+        .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("song.mp3");
+        let coordinator = DownloadCoordinator::new(ApiClient::new(Default::default()));
+        let events: Vec<DownloadEvent> = coordinator
+            .download(DownloadRequest::new(url, path.clone()))
+            .collect()
+            .await;
+
+        assert!(matches!(
+            events.last(),
+            Some(DownloadEvent::Completed { .. })
+        ));
+        assert_eq!(std::fs::read(&path).unwrap(), b"0123456789");
+    }
+
+    #[tokio::test]
+    async fn test_download_honors_its_retry_budget() {
+        let url = spawn_raw_server(vec![
+            TRUNCATED_BODY,
+            b"HTTP/1.1 200 OK\r\naccept-ranges: bytes\r\ncontent-length: 10\r\n\r\n",
+            b"HTTP/1.1 206 Partial Content\r\ncontent-length: 5\r\n\r\n56789",
+        ])
+        .await;
+
+        let reconnects = Arc::new(AtomicUsize::new(0));
+        let counter = reconnects.clone();
+        let dir = tempfile::tempdir().unwrap();
+        let coordinator = DownloadCoordinator::new(ApiClient::new(ApiConfig {
+            download_retries: 0,
+            ..Default::default()
+        }))
+        .with_status_hook(move |status| {
+            if status.contains("reconnecting") {
+                counter.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+        let events: Vec<DownloadEvent> = coordinator
+            .download(DownloadRequest::new(url, dir.path().join("song.mp3")))
+            .collect()
+            .await;
+
+        assert!(matches!(events.last(), Some(DownloadEvent::Failed(_))));
+        assert_eq!(reconnects.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_download_to_backup_copy() {
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock("GET", "/file.mp3")
+            .with_body("0123456789")
+            .create_async()
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("song.mp3");
+        let backup = dir.path().join("backup.mp3");
+        let missing = dir.path().join("missing").join("song.mp3");
+        let coordinator = DownloadCoordinator::new(ApiClient::new(Default::default()));
+
+        let events: Vec<DownloadEvent> = coordinator
+            .download(
+                DownloadRequest::new(format!("{}/file.mp3", server.url()), path.clone())
+                    .backups(vec![backup.clone()], TeePolicy::FailFast),
+            )
+            .collect()
+            .await;
+        assert!(
+            matches!(events.last(), Some(DownloadEvent::Completed { path: p, .. }) if *p == path)
+        );
+        assert_eq!(std::fs::read(&path).unwrap(), b"0123456789");
+        assert_eq!(std::fs::read(&backup).unwrap(), b"0123456789");
+
+        // An unusable backup location is caught before anything is downloaded
+        let events: Vec<DownloadEvent> = coordinator
+            .download(
+                DownloadRequest::new(format!("{}/file.mp3", server.url()), path.clone())
+                    .backups(vec![missing], TeePolicy::BestEffort),
+            )
+            .collect()
+            .await;
+        assert!(matches!(events.last(), Some(DownloadEvent::Failed(_))));
+    }
+
+    #[tokio::test]
+    async fn test_redownload_keeps_existing_tags() {
+        use id3::TagLike;
+
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock("GET", "/file.mp3")
+            .with_body("0123456789")
+            .create_async()
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("song.mp3");
+        std::fs::write(&path, b"old low quality audio").unwrap();
+        let mut tag = id3::Tag::new();
+        tag.set_title("Song");
+        tag.set_text("TIT1", "Favourites");
+        tag.write_to_path(&path, id3::Version::Id3v24).unwrap();
+
+        let coordinator =
+            DownloadCoordinator::new(ApiClient::new(Default::default())).keep_existing_tags(true);
+        let events: Vec<DownloadEvent> = coordinator
+            .download(DownloadRequest::new(
+                format!("{}/file.mp3", server.url()),
+                path.clone(),
+            ))
+            .collect()
+            .await;
+
+        assert!(matches!(
+            events.last(),
+            Some(DownloadEvent::Completed { .. })
+        ));
+        let tag = id3::Tag::read_from_path(&path).unwrap();
+        assert_eq!(tag.title(), Some("Song"));
+        assert_eq!(
+            tag.get("TIT1").and_then(|f| f.content().text()),
+            Some("Favourites")
+        );
+        assert!(std::fs::read(&path).unwrap().ends_with(b"0123456789"));
+    }
+
+    #[tokio::test]
+    async fn test_output_moved_mid_download() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // Serve half the body, then hold the rest back until the test says so
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/file.mp3", listener.local_addr().unwrap());
+        let (resume_tx, resume_rx) = tokio::sync::oneshot::channel::<()>();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 1024];
+            let _ = socket.read(&mut request).await.unwrap();
+            socket.write_all(TRUNCATED_BODY).await.unwrap();
+            let _ = resume_rx.await;
+            socket.write_all(b"56789").await.unwrap();
+        });
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("song.mp3");
+        let moved = dir.path().join("moved.mp3");
+        let coordinator = DownloadCoordinator::new(ApiClient::new(Default::default()));
+        let mut events = coordinator.download(DownloadRequest::new(url, path.clone()));
+
+        while !matches!(
+            events.next().await,
+            Some(DownloadEvent::Progress { downloaded, .. }) if downloaded > 0
+        ) {}
+        std::fs::rename(ResumeRecord::partial_path(&path), &moved).unwrap();
+        resume_tx.send(()).unwrap();
+        let events: Vec<DownloadEvent> = events.collect().await;
+
+        assert!(matches!(
+            events.last(),
+            Some(DownloadEvent::Failed(AppError::Io(message))) if message == OUTPUT_DISAPPEARED
+        ));
+        assert!(!path.exists());
+        assert!(!ResumeRecord::partial_path(&path).exists());
+        assert!(!ResumeRecord::record_path(&path).exists());
+    }
+
+    #[tokio::test]
+    async fn test_download_does_not_resume_without_range_support() {
+        let url = spawn_raw_server(vec![
+            TRUNCATED_BODY,
+            b"HTTP/1.1 200 OK\r\ncontent-length: 10\r\n\r\n",
+        ])
+        .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("song.mp3");
+        let coordinator = DownloadCoordinator::new(ApiClient::new(Default::default()));
+        let events: Vec<DownloadEvent> = coordinator
+            .download(DownloadRequest::new(url, path.clone()))
+            .collect()
+            .await;
+
+        assert!(matches!(events.last(), Some(DownloadEvent::Failed(_))));
+        // The half that arrived stays in the partial file, never under the final name
+        assert!(!path.exists());
+        assert_eq!(
+            std::fs::read(ResumeRecord::partial_path(&path)).unwrap(),
+            b"01234"
+        );
+    }
+
+    #[test]
+    fn test_preview_filename_follows_template() {
+        let coordinator = DownloadCoordinator::new(ApiClient::new(Default::default()));
+        assert_eq!(
+            coordinator.preview_filename("AC/DC - T.N.T.", "z0vCwGUZe1I"),
+            "AC_DC - T.N.T.mp3"
+        );
+
+        let coordinator = coordinator.filename_template(FilenameTemplate::new(
+            Some("{title} [{quality}]".to_string()),
+            crate::domain::AudioQuality::Kbps(192),
+            crate::domain::OutputFormat::Mp3,
+        ));
+        assert_eq!(
+            coordinator.preview_filename("Artist: Song", "z0vCwGUZe1I"),
+            "Artist_ Song [192kbps].mp3"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pause_saves_resume_record() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // Serve half the body, then leave the connection hanging
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/file.mp3", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 1024];
+            let _ = socket.read(&mut request).await.unwrap();
+            socket.write_all(TRUNCATED_BODY).await.unwrap();
+            std::future::pending::<()>().await;
+        });
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("song.mp3");
+        let pause = CancellationToken::new();
+        let coordinator = DownloadCoordinator::new(ApiClient::new(Default::default()));
+        let mut events = coordinator.download(
+            DownloadRequest::new(url.clone(), path.clone())
+                .source("https://youtu.be/z0vCwGUZe1I".to_string())
+                .pause_on(pause.clone()),
+        );
+
+        while !matches!(
+            events.next().await,
+            Some(DownloadEvent::Progress { downloaded, .. }) if downloaded > 0
+        ) {}
+        pause.cancel();
+        let events: Vec<DownloadEvent> = events.collect().await;
+
+        let Some(DownloadEvent::Paused(record)) = events.last() else {
+            panic!("expected a pause, got {:?}", events);
+        };
+        assert_eq!(record.downloaded_bytes, 5);
+        assert_eq!(
+            record.source.as_deref(),
+            Some("https://youtu.be/z0vCwGUZe1I")
+        );
+        assert_eq!(ResumeRecord::load(&path).await.as_ref(), Some(record));
+        assert_eq!(
+            std::fs::read(ResumeRecord::partial_path(&path)).unwrap(),
+            b"01234"
+        );
+        // Nothing appears under the final name until the download is complete
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_removes_partial_file() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/file.mp3", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 1024];
+            let _ = socket.read(&mut request).await.unwrap();
+            socket.write_all(TRUNCATED_BODY).await.unwrap();
+            std::future::pending::<()>().await;
+        });
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("song.mp3");
+        let cancel = CancellationToken::new();
+        let coordinator = DownloadCoordinator::new(ApiClient::new(Default::default()));
+        let mut events =
+            coordinator.download(DownloadRequest::new(url, path.clone()).cancel_on(cancel.clone()));
+
+        while !matches!(
+            events.next().await,
+            Some(DownloadEvent::Progress { downloaded, .. }) if downloaded > 0
+        ) {}
+        cancel.cancel();
+        let events: Vec<DownloadEvent> = events.collect().await;
+
+        assert!(
+            matches!(events.as_slice(), [DownloadEvent::Cancelled]),
+            "expected only a cancellation, got {:?}",
+            events
+        );
+        assert!(!path.exists());
+        assert!(ResumeRecord::load(&path).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_plan_quality_and_format_reach_completion() {
+        let mut server = mockito::Server::new_async().await;
+        let config = mock_backend(&mut server).await;
+        let _file = server
+            .mock("GET", "/file.mp3")
+            .with_body("0123456789")
+            .create_async()
+            .await;
+
+        let coordinator = DownloadCoordinator::new(ApiClient::new(config)).filename_template(
+            FilenameTemplate::new(None, AudioQuality::Kbps(192), OutputFormat::Mp3),
+        );
+        let plan = coordinator
+            .prepare_download("https://youtu.be/z0vCwGUZe1I".to_string())
+            .await
+            .unwrap();
+        assert_eq!(plan.quality, AudioQuality::Kbps(192));
+        assert_eq!(plan.format, OutputFormat::Mp3);
+
+        let dir = tempfile::tempdir().unwrap();
+        let events: Vec<DownloadEvent> = coordinator
+            .download(DownloadRequest::from_plan(
+                plan,
+                dir.path().join("song.mp3"),
+            ))
+            .collect()
+            .await;
+        assert!(matches!(
+            events.last(),
+            Some(DownloadEvent::Completed {
+                quality: AudioQuality::Kbps(192),
+                format: OutputFormat::Mp3,
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_resume_refreshes_expired_link() {
+        let mut server = mockito::Server::new_async().await;
+        let config = mock_backend(&mut server).await;
+        let _expired = server
+            .mock("GET", "/expired.mp3")
+            .with_status(403)
+            .create_async()
+            .await;
+        let _fresh = server
+            .mock("GET", "/file.mp3")
+            .match_header("range", "bytes=5-")
+            .with_status(206)
+            .with_header("content-range", "bytes 5-9/10")
+            .with_body("56789")
+            .create_async()
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("song.mp3");
+        std::fs::write(ResumeRecord::partial_path(&path), b"01234").unwrap();
+        let record = ResumeRecord {
+            url: format!("{}/expired.mp3", server.url()),
+            source: Some("https://youtu.be/z0vCwGUZe1I".to_string()),
+            path: path.clone(),
+            downloaded_bytes: 5,
+            total: Some(10),
+        };
+        record.save().await.unwrap();
+
+        let coordinator = DownloadCoordinator::new(ApiClient::new(config));
+        let events: Vec<DownloadEvent> = coordinator
+            .download(DownloadRequest::resume(record))
+            .collect()
+            .await;
+
+        assert!(matches!(
+            events.last(),
+            Some(DownloadEvent::Completed { .. })
+        ));
+        assert_eq!(std::fs::read(&path).unwrap(), b"0123456789");
+    }
+}
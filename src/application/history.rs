@@ -0,0 +1,199 @@
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    domain::{AudioQuality, OutputFormat},
+    utils::{extract_video_id, get_timestamp, normalize_youtube_url},
+};
+
+const HISTORY_FILE: &str = "history.jsonl";
+
+/// One finished download
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// Canonical watch URL, without share/tracking parameters
+    pub url: String,
+    pub video_id: String,
+    /// Video title, if known (absent in entries from older versions)
+    #[serde(default)]
+    pub title: Option<String>,
+    pub path: PathBuf,
+    /// Bytes downloaded, if known (absent in entries from older versions)
+    #[serde(default)]
+    pub size: Option<u64>,
+    /// Quality and format the file was requested in (absent in entries from older versions)
+    #[serde(default)]
+    pub quality: Option<AudioQuality>,
+    #[serde(default)]
+    pub format: Option<OutputFormat>,
+    /// Unix timestamp (seconds)
+    pub downloaded_at: u64,
+}
+
+impl HistoryEntry {
+    /// Entry for `youtube_url` saved to `path`, or `None` if it isn't a video URL
+    pub fn new(youtube_url: &str, path: PathBuf, size: Option<u64>) -> Option<Self> {
+        Some(Self {
+            url: normalize_youtube_url(youtube_url)?,
+            video_id: extract_video_id(youtube_url)?,
+            title: None,
+            path,
+            size,
+            quality: None,
+            format: None,
+            downloaded_at: get_timestamp(),
+        })
+    }
+
+    pub fn title(mut self, title: Option<String>) -> Self {
+        self.title = title;
+        self
+    }
+
+    /// Title to list the entry under, falling back to the file name
+    pub fn label(&self) -> String {
+        match &self.title {
+            Some(title) => title.clone(),
+            None => self
+                .path
+                .file_name()
+                .unwrap_or(self.path.as_os_str())
+                .to_string_lossy()
+                .into_owned(),
+        }
+    }
+
+    /// Note the quality and format the download was requested in
+    pub fn output(mut self, quality: AudioQuality, format: OutputFormat) -> Self {
+        self.quality = Some(quality);
+        self.format = Some(format);
+        self
+    }
+}
+
+/// Append-only download history, one JSON entry per line
+#[derive(Debug, Clone)]
+pub struct HistoryStore {
+    file: PathBuf,
+}
+
+impl HistoryStore {
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            file: dir.join(HISTORY_FILE),
+        }
+    }
+
+    /// Store in the user's config directory, if there is one
+    pub fn open_default() -> Option<Self> {
+        crate::utils::config_dir().map(|dir| Self::new(&dir))
+    }
+
+    pub fn append(&self, entry: &HistoryEntry) -> std::io::Result<()> {
+        if let Some(dir) = self.file.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.file)?
+            .write_all(&line)
+    }
+
+    /// Most recent download of `video_id`, if any
+    pub fn find(&self, video_id: &str) -> Option<HistoryEntry> {
+        self.entries()
+            .into_iter()
+            .rev()
+            .find(|entry| entry.video_id == video_id)
+    }
+
+    /// Up to `limit` entries, newest first
+    pub fn recent(&self, limit: usize) -> Vec<HistoryEntry> {
+        self.entries().into_iter().rev().take(limit).collect()
+    }
+
+    /// Every readable entry, oldest first
+    ///
+    /// A missing file is an empty history; lines that don't parse (e.g. cut short by a
+    /// crash mid-write) are skipped rather than spoiling the rest.
+    fn entries(&self) -> Vec<HistoryEntry> {
+        std::fs::read_to_string(&self.file)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_share_url_is_stored_in_canonical_form() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = HistoryStore::new(dir.path());
+        let entry = HistoryEntry::new(
+            "https://youtu.be/z0vCwGUZe1I?si=Xb3kQ9_referral&t=42",
+            dir.path().join("song.mp3"),
+            Some(4096),
+        )
+        .unwrap()
+        .output(AudioQuality::Kbps(192), OutputFormat::Mp3);
+        store.append(&entry).unwrap();
+
+        let stored = store.find("z0vCwGUZe1I").unwrap();
+        assert_eq!(stored.url, "https://www.youtube.com/watch?v=z0vCwGUZe1I");
+        assert_eq!(stored, entry);
+        assert_eq!(store.find("aaaaaaaaaaa"), None);
+        assert!(HistoryEntry::new("not a url", dir.path().join("x.mp3"), None).is_none());
+    }
+
+    #[test]
+    fn test_recent_lists_newest_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = HistoryStore::new(dir.path());
+        assert!(store.recent(10).is_empty());
+
+        for (id, title) in [("aaaaaaaaaaa", Some("First")), ("bbbbbbbbbbb", None)] {
+            let entry = HistoryEntry::new(
+                &format!("https://youtu.be/{}", id),
+                dir.path().join(format!("{}.mp3", id)),
+                None,
+            )
+            .unwrap()
+            .title(title.map(String::from));
+            store.append(&entry).unwrap();
+        }
+
+        let recent = store.recent(10);
+        assert_eq!(recent.len(), 2);
+        assert_eq!(recent[0].label(), "bbbbbbbbbbb.mp3");
+        assert_eq!(recent[1].label(), "First");
+        assert_eq!(store.recent(1), recent[..1]);
+    }
+
+    #[test]
+    fn test_corrupt_lines_are_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = HistoryStore::new(dir.path());
+        std::fs::write(dir.path().join(HISTORY_FILE), "{not json\n\0\0\0\n").unwrap();
+        assert!(store.recent(10).is_empty());
+
+        let entry = HistoryEntry::new(
+            "https://youtu.be/z0vCwGUZe1I",
+            dir.path().join("song.mp3"),
+            None,
+        )
+        .unwrap();
+        store.append(&entry).unwrap();
+        assert_eq!(store.recent(10), vec![entry]);
+    }
+}
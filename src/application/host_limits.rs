@@ -0,0 +1,90 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Downloads allowed at once from a single host when nothing else is configured
+pub const DEFAULT_MAX_PER_HOST: usize = 2;
+
+/// Caps concurrent downloads per host, so one CDN isn't hit by every stream of a batch
+#[derive(Debug, Clone)]
+pub struct HostLimiter {
+    max_per_host: usize,
+    hosts: Arc<Mutex<HashMap<String, Arc<Semaphore>>>>,
+}
+
+impl Default for HostLimiter {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_PER_HOST)
+    }
+}
+
+impl HostLimiter {
+    pub fn new(max_per_host: usize) -> Self {
+        Self {
+            max_per_host: max_per_host.max(1),
+            hosts: Arc::default(),
+        }
+    }
+
+    /// Wait for a free slot on the host of `url`; the slot is held until the permit drops
+    pub async fn acquire(&self, url: &str) -> OwnedSemaphorePermit {
+        let host = url::Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_ascii_lowercase))
+            .unwrap_or_default();
+        let semaphore = self
+            .hosts
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(host)
+            .or_insert_with(|| Arc::new(Semaphore::new(self.max_per_host)))
+            .clone();
+
+        semaphore
+            .acquire_owned()
+            .await
+            .expect("host semaphores are never closed")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_per_host_limit_is_respected() {
+        let limiter = HostLimiter::new(2);
+        let cdn = Arc::new((AtomicUsize::new(0), AtomicUsize::new(0)));
+        let other = Arc::new((AtomicUsize::new(0), AtomicUsize::new(0)));
+
+        let mut tasks = Vec::new();
+        for i in 0..8 {
+            let (url, counters) = if i % 4 == 3 {
+                ("https://other.example.net/b.mp3", other.clone())
+            } else {
+                ("https://CDN.example.com/a.mp3", cdn.clone())
+            };
+            let limiter = limiter.clone();
+            tasks.push(tokio::spawn(async move {
+                let _permit = limiter.acquire(url).await;
+                let running = counters.0.fetch_add(1, Ordering::SeqCst) + 1;
+                counters.1.fetch_max(running, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                counters.0.fetch_sub(1, Ordering::SeqCst);
+            }));
+        }
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        // Six tasks on one host never ran more than two at a time...
+        assert_eq!(cdn.1.load(Ordering::SeqCst), 2);
+        // ...and didn't hold up the other host, which ran both of its tasks together
+        assert_eq!(other.1.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,46 @@
+use std::path::Path;
+
+use id3::{Tag, Version};
+
+use crate::domain::AppError;
+
+/// ID3 tag of the file at `path`, if there is one and it can be read
+pub fn read_existing_tag(path: &Path) -> Option<Tag> {
+    Tag::read_from_path(path).ok()
+}
+
+/// Replace the tag of the file at `path` with `tag`
+pub fn apply_tag(path: &Path, tag: &Tag) -> Result<(), AppError> {
+    tag.write_to_path(path, Version::Id3v24)
+        .map_err(|e| AppError::Io(format!("Failed to write tags: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use id3::TagLike;
+
+    use super::*;
+
+    #[test]
+    fn test_untagged_file_has_no_tag() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("song.mp3");
+
+        assert!(read_existing_tag(&path).is_none());
+        std::fs::write(&path, b"no tag here").unwrap();
+        assert!(read_existing_tag(&path).is_none());
+    }
+
+    #[test]
+    fn test_apply_tag_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("song.mp3");
+        std::fs::write(&path, b"audio").unwrap();
+
+        let mut tag = Tag::new();
+        tag.set_title("Song");
+        apply_tag(&path, &tag).unwrap();
+
+        assert_eq!(read_existing_tag(&path).unwrap().title(), Some("Song"));
+    }
+}
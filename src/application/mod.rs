@@ -1,3 +1,39 @@
+mod artwork;
 mod download_coordinator;
+mod history;
+mod host_limits;
+mod metadata;
+mod naming;
+mod output;
+mod paused;
+mod playlist;
+mod post_download;
+mod progress;
+mod queue;
+mod report;
+mod resume;
+mod save_dir;
+mod settings;
+mod sound;
+mod tags;
+mod tee;
+#[cfg(test)]
+pub(crate) mod test_support;
+mod transcode;
 
-pub use download_coordinator::{DownloadCoordinator, DownloadEvent};
+pub use download_coordinator::{DownloadCoordinator, DownloadEvent, DownloadRequest, PrepareStage};
+pub use history::{HistoryEntry, HistoryStore};
+pub use naming::{EmptyNameFallback, FilenameTemplate};
+pub use paused::PausedStore;
+pub use playlist::append_to_playlist;
+pub use post_download::PostDownloadAction;
+pub use progress::{ProgressAggregator, RateMeter};
+pub use queue::{DownloadQueue, QueuedItem};
+pub use report::{BatchReport, ItemOutcome};
+pub use resume::ResumeRecord;
+pub use save_dir::SaveDirStore;
+pub use settings::Settings;
+pub use sound::Sound;
+pub use tags::TrackTags;
+pub use tee::TeePolicy;
+pub use transcode::{transcode_stream, TranscodeEvent, TranscodeParams};
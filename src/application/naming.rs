@@ -0,0 +1,278 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    domain::{AudioQuality, OutputFormat},
+    utils::{get_timestamp, sanitize_filename},
+};
+
+/// Template used when none is configured: just the video title
+pub const DEFAULT_TEMPLATE: &str = "{title}";
+
+/// What to name a file whose title has nothing usable left once sanitized
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum EmptyNameFallback {
+    /// The video ID, e.g. `z0vCwGUZe1I.mp3`
+    #[default]
+    VideoId,
+    /// `untitled.mp3`
+    Untitled,
+    /// The download time, e.g. `download-1767225600.mp3`
+    Timestamp,
+}
+
+impl EmptyNameFallback {
+    fn name(self, video_id: &str) -> String {
+        match self {
+            EmptyNameFallback::VideoId => video_id.to_string(),
+            EmptyNameFallback::Untitled => "untitled".to_string(),
+            EmptyNameFallback::Timestamp => format!("download-{}", get_timestamp()),
+        }
+    }
+}
+
+impl std::str::FromStr for EmptyNameFallback {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "id" => Ok(EmptyNameFallback::VideoId),
+            "untitled" => Ok(EmptyNameFallback::Untitled),
+            "timestamp" => Ok(EmptyNameFallback::Timestamp),
+            _ => Err(format!(
+                "unknown fallback '{}' (expected id, untitled or timestamp)",
+                value
+            )),
+        }
+    }
+}
+
+/// Builds output filenames from a template with `{title}`, `{quality}` and `{format}` tokens
+#[derive(Debug, Clone)]
+pub struct FilenameTemplate {
+    template: String,
+    quality: AudioQuality,
+    format: OutputFormat,
+    fallback: EmptyNameFallback,
+}
+
+impl Default for FilenameTemplate {
+    fn default() -> Self {
+        Self::new(None, AudioQuality::default(), OutputFormat::default())
+    }
+}
+
+impl FilenameTemplate {
+    pub fn new(template: Option<String>, quality: AudioQuality, format: OutputFormat) -> Self {
+        Self {
+            template: template.unwrap_or_else(|| DEFAULT_TEMPLATE.to_string()),
+            quality,
+            format,
+            fallback: EmptyNameFallback::default(),
+        }
+    }
+
+    /// Name files after `fallback` when their title is empty or all illegal characters
+    pub fn empty_name_fallback(mut self, fallback: EmptyNameFallback) -> Self {
+        self.fallback = fallback;
+        self
+    }
+
+    /// Name files for `quality` and `format` instead
+    pub fn output(mut self, quality: AudioQuality, format: OutputFormat) -> Self {
+        self.quality = quality;
+        self.format = format;
+        self
+    }
+
+    pub fn quality(&self) -> AudioQuality {
+        self.quality
+    }
+
+    pub fn format(&self) -> OutputFormat {
+        self.format
+    }
+
+    /// Filename (with extension) for the track `video_id` titled `title`
+    pub fn render(&self, title: &str, video_id: &str) -> String {
+        let fallback;
+        let title = if is_blank(&sanitize_filename(title)) {
+            fallback = self.fallback.name(video_id);
+            fallback.as_str()
+        } else {
+            title
+        };
+
+        // Expand tokens first so characters they introduce get sanitized too
+        let name = self
+            .template
+            .replace("{title}", title)
+            .replace("{quality}", &self.quality.label())
+            .replace("{format}", self.format.extension());
+
+        format!(
+            "{}.{}",
+            sanitize_filename(&name).trim_matches(|c| c == '.' || c == ' '),
+            self.format.extension()
+        )
+    }
+}
+
+/// Whether a sanitized name is nothing but replaced characters, dots and spaces
+fn is_blank(name: &str) -> bool {
+    name.chars()
+        .all(|c| c == '_' || c == '.' || c.is_whitespace())
+}
+
+/// `path` if nothing is there yet, otherwise the first free `name (N).ext` next to it
+pub fn resolve_unique_path(path: &Path) -> PathBuf {
+    if !path.exists() {
+        return path.to_path_buf();
+    }
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+
+    (1..)
+        .map(|n| path.with_file_name(format!("{} ({}){}", stem, n, extension)))
+        .find(|candidate| !candidate.exists())
+        .expect("some numbered name is free")
+}
+
+/// `path` with `.extension` appended unless it already ends in it (in any case)
+pub fn ensure_extension(path: &Path, extension: &str) -> PathBuf {
+    match path.extension() {
+        Some(ext) if ext.eq_ignore_ascii_case(extension) => path.to_path_buf(),
+        _ => {
+            let mut path = path.as_os_str().to_owned();
+            path.push(".");
+            path.push(extension);
+            PathBuf::from(path)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ensure_extension() {
+        let path = Path::new("/music/Song.mp3");
+        assert_eq!(ensure_extension(path, "mp3"), path);
+        assert_eq!(
+            ensure_extension(Path::new("/music/Song.MP3"), "mp3"),
+            Path::new("/music/Song.MP3")
+        );
+        assert_eq!(
+            ensure_extension(Path::new("/music/Song"), "mp3"),
+            Path::new("/music/Song.mp3")
+        );
+        // Dots in the name aren't mistaken for an extension to replace
+        assert_eq!(
+            ensure_extension(Path::new("/music/Mr. Song"), "mp3"),
+            Path::new("/music/Mr. Song.mp3")
+        );
+    }
+
+    #[test]
+    fn test_resolve_unique_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let song = dir.path().join("Song.mp3");
+        assert_eq!(resolve_unique_path(&song), song);
+
+        std::fs::write(&song, b"first").unwrap();
+        assert_eq!(resolve_unique_path(&song), dir.path().join("Song (1).mp3"));
+
+        std::fs::write(dir.path().join("Song (1).mp3"), b"second").unwrap();
+        std::fs::write(dir.path().join("Song (2).mp3"), b"third").unwrap();
+        assert_eq!(resolve_unique_path(&song), dir.path().join("Song (3).mp3"));
+
+        let bare = dir.path().join("Song");
+        std::fs::write(&bare, b"no extension").unwrap();
+        assert_eq!(resolve_unique_path(&bare), dir.path().join("Song (1)"));
+    }
+
+    #[test]
+    fn test_default_template_uses_title() {
+        let naming = FilenameTemplate::default();
+        assert_eq!(
+            naming.render("Artist - Song", "z0vCwGUZe1I"),
+            "Artist - Song.mp3"
+        );
+    }
+
+    #[test]
+    fn test_empty_name_fallbacks() {
+        let render = |fallback| {
+            FilenameTemplate::default()
+                .empty_name_fallback(fallback)
+                .render(" ??/:* ", "z0vCwGUZe1I")
+        };
+
+        assert_eq!(render(EmptyNameFallback::VideoId), "z0vCwGUZe1I.mp3");
+        assert_eq!(render(EmptyNameFallback::Untitled), "untitled.mp3");
+        let timestamped = render(EmptyNameFallback::Timestamp);
+        let stamp = timestamped
+            .strip_prefix("download-")
+            .and_then(|rest| rest.strip_suffix(".mp3"))
+            .unwrap();
+        assert!(stamp.parse::<u64>().unwrap() > 1_700_000_000);
+
+        // A real title is left alone, whatever the fallback
+        assert_eq!(
+            FilenameTemplate::default()
+                .empty_name_fallback(EmptyNameFallback::Untitled)
+                .render("_Song_", "z0vCwGUZe1I"),
+            "_Song_.mp3"
+        );
+        assert_eq!(
+            "timestamp".parse::<EmptyNameFallback>(),
+            Ok(EmptyNameFallback::Timestamp)
+        );
+        assert!("title".parse::<EmptyNameFallback>().is_err());
+    }
+
+    #[test]
+    fn test_quality_token() {
+        let naming = FilenameTemplate::new(
+            Some("{title} [{quality}]".to_string()),
+            AudioQuality::Kbps(320),
+            OutputFormat::Mp3,
+        );
+        assert_eq!(naming.render("Song", "z0vCwGUZe1I"), "Song [320kbps].mp3");
+
+        let naming = FilenameTemplate::new(
+            Some("{title} [{quality}]".to_string()),
+            AudioQuality::Source,
+            OutputFormat::Mp3,
+        );
+        assert_eq!(naming.render("Song", "z0vCwGUZe1I"), "Song [source].mp3");
+    }
+
+    #[test]
+    fn test_format_token() {
+        let naming = FilenameTemplate::new(
+            Some("{format}/{title}".to_string()),
+            AudioQuality::Source,
+            OutputFormat::Mp3,
+        );
+        assert_eq!(naming.render("Song", "z0vCwGUZe1I"), "mp3_Song.mp3");
+    }
+
+    #[test]
+    fn test_title_is_sanitized_after_expansion() {
+        let naming = FilenameTemplate::new(
+            Some("{title} ({quality})".to_string()),
+            AudioQuality::Kbps(128),
+            OutputFormat::Mp3,
+        );
+        assert_eq!(
+            naming.render("AC/DC: Live?", "z0vCwGUZe1I"),
+            "AC_DC_ Live_ (128kbps).mp3"
+        );
+    }
+}
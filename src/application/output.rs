@@ -0,0 +1,331 @@
+use std::{io::SeekFrom, path::Path};
+
+use tokio::io::{AsyncSeekExt, AsyncWriteExt, BufWriter};
+
+use crate::domain::AppError;
+
+/// Write buffer used for local disks
+const LOCAL_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Write buffer used for network and cloud-synced folders, where each write is a round-trip
+const NETWORK_BUFFER_SIZE: usize = 4 * 1024 * 1024;
+
+/// How often buffered data is pushed out explicitly on network folders
+const NETWORK_FLUSH_EVERY: u64 = 32 * 1024 * 1024;
+
+/// Headroom kept free on the target disk by default, so a download never fills it up
+pub const DEFAULT_MIN_FREE_MARGIN: u64 = 100 * 1024 * 1024;
+
+/// Error message for an output file or folder that was moved or deleted mid-download
+pub const OUTPUT_DISAPPEARED: &str = "output location disappeared";
+
+/// Filesystem types that are backed by a remote server
+const NETWORK_FS_TYPES: &[&str] = &[
+    "nfs", "nfs4", "cifs", "smb3", "smbfs", "sshfs", "davfs", "9p",
+];
+
+/// Buffering strategy for the output file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WriteTuning {
+    pub buffer_size: usize,
+    /// Flush after this many unflushed bytes, if set
+    pub flush_every: Option<u64>,
+}
+
+impl WriteTuning {
+    pub fn local() -> Self {
+        Self {
+            buffer_size: LOCAL_BUFFER_SIZE,
+            flush_every: None,
+        }
+    }
+
+    pub fn network() -> Self {
+        Self {
+            buffer_size: NETWORK_BUFFER_SIZE,
+            flush_every: Some(NETWORK_FLUSH_EVERY),
+        }
+    }
+
+    /// Pick tuning for `path`, with an optional buffer size override
+    pub fn for_path(path: &Path, buffer_size: Option<usize>) -> Self {
+        let mut tuning = if is_network_path(path) {
+            Self::network()
+        } else {
+            Self::local()
+        };
+
+        if let Some(size) = buffer_size {
+            tuning.buffer_size = size;
+        }
+        tuning
+    }
+}
+
+/// Buffered output file that flushes periodically and syncs on finish
+pub struct OutputWriter {
+    // Boxed so the download state machine stays small while it's moved between steps
+    inner: Box<BufWriter<tokio::fs::File>>,
+    flush_every: Option<u64>,
+    unflushed: u64,
+}
+
+impl OutputWriter {
+    pub async fn create(path: &Path, tuning: WriteTuning) -> Result<Self, AppError> {
+        let file = create_output_file(path).await?;
+
+        Ok(Self {
+            inner: Box::new(BufWriter::with_capacity(tuning.buffer_size, file)),
+            flush_every: tuning.flush_every,
+            unflushed: 0,
+        })
+    }
+
+    /// Reopen a partially written file, dropping anything past `offset`
+    pub async fn resume(path: &Path, offset: u64, tuning: WriteTuning) -> Result<Self, AppError> {
+        reject_symlink(path).await?;
+
+        let mut file = tokio::fs::OpenOptions::new()
+            .write(true)
+            .open(path)
+            .await
+            .map_err(|e| AppError::Io(format!("Failed to open file: {}", e)))?;
+        file.set_len(offset)
+            .await
+            .map_err(|e| AppError::Io(format!("Failed to truncate file: {}", e)))?;
+        file.seek(SeekFrom::End(0))
+            .await
+            .map_err(|e| AppError::Io(format!("Failed to seek file: {}", e)))?;
+
+        Ok(Self {
+            inner: Box::new(BufWriter::with_capacity(tuning.buffer_size, file)),
+            flush_every: tuning.flush_every,
+            unflushed: 0,
+        })
+    }
+
+    pub async fn write(&mut self, chunk: &[u8]) -> std::io::Result<()> {
+        self.inner.write_all(chunk).await?;
+        self.unflushed += chunk.len() as u64;
+
+        if self
+            .flush_every
+            .is_some_and(|every| self.unflushed >= every)
+        {
+            self.inner.flush().await?;
+            self.unflushed = 0;
+        }
+        Ok(())
+    }
+
+    /// Flush everything still buffered and sync it to disk
+    pub async fn finish(&mut self) -> std::io::Result<()> {
+        self.inner.flush().await?;
+        self.inner.get_ref().sync_all().await
+    }
+}
+
+/// Create (or truncate) the output file, refusing to follow a symlink at `path`
+async fn create_output_file(path: &Path) -> Result<tokio::fs::File, AppError> {
+    reject_symlink(path).await?;
+
+    tokio::fs::File::create(path)
+        .await
+        .map_err(|e| AppError::Io(format!("Failed to create file: {}", e)))
+}
+
+/// Describe a failed write to `path`, recognising the target having been moved or deleted
+pub fn output_error(path: &Path, context: &str, error: std::io::Error) -> AppError {
+    let parent_gone = path
+        .parent()
+        .is_some_and(|dir| !dir.as_os_str().is_empty() && !dir.exists());
+    if error.kind() == std::io::ErrorKind::NotFound || parent_gone {
+        return AppError::Io(OUTPUT_DISAPPEARED.to_string());
+    }
+    AppError::Io(format!("{}: {}", context, error))
+}
+
+/// Fail early if the disk holding `path` can't take `remaining` more bytes (an unknown
+/// amount when `None`) and still keep `margin` free
+pub fn check_free_space(path: &Path, remaining: Option<u64>, margin: u64) -> Result<(), AppError> {
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+
+    // Not every filesystem reports free space; don't block the download on that
+    match fs2::available_space(dir) {
+        Ok(available) => ensure_free_space(available, required_free_space(remaining, margin)),
+        Err(_) => Ok(()),
+    }
+}
+
+fn required_free_space(remaining: Option<u64>, margin: u64) -> u64 {
+    remaining.unwrap_or(0).saturating_add(margin)
+}
+
+fn ensure_free_space(available: u64, needed: u64) -> Result<(), AppError> {
+    if available < needed {
+        return Err(AppError::InsufficientSpace { needed, available });
+    }
+    Ok(())
+}
+
+async fn reject_symlink(path: &Path) -> Result<(), AppError> {
+    if let Ok(metadata) = tokio::fs::symlink_metadata(path).await {
+        if metadata.file_type().is_symlink() {
+            return Err(AppError::SymlinkTarget(path.display().to_string()));
+        }
+    }
+    Ok(())
+}
+
+/// Best-effort guess whether `path` lives on a network or cloud-synced filesystem
+fn is_network_path(path: &Path) -> bool {
+    let raw = path.to_string_lossy();
+    if raw.starts_with(r"\\") {
+        // Windows UNC share
+        return true;
+    }
+
+    let cloud_folder = path.components().any(|c| {
+        let name = c.as_os_str().to_string_lossy().to_ascii_lowercase();
+        name.starts_with("onedrive") || name == "dropbox" || name == "google drive"
+    });
+    if cloud_folder {
+        return true;
+    }
+
+    std::fs::read_to_string("/proc/mounts")
+        .map(|mounts| is_network_mount(&mounts, path))
+        .unwrap_or(false)
+}
+
+/// Check the filesystem type of the longest mount point containing `path`
+fn is_network_mount(mounts: &str, path: &Path) -> bool {
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let _device = fields.next()?;
+            let mount_point = fields.next()?;
+            let fs_type = fields.next()?;
+            Some((Path::new(mount_point), fs_type))
+        })
+        .filter(|(mount_point, _)| path.starts_with(mount_point))
+        .max_by_key(|(mount_point, _)| mount_point.as_os_str().len())
+        .is_some_and(|(_, fs_type)| {
+            NETWORK_FS_TYPES.contains(&fs_type) || fs_type.starts_with("fuse.rclone")
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MOUNTS: &str = "\
+/dev/sda1 / ext4 rw 0 0
+server:/export /mnt/nas nfs4 rw 0 0
+remote: /home/me/cloud fuse.rclone rw 0 0
+/dev/sdb1 /mnt/nas/local ext4 rw 0 0
+";
+
+    #[test]
+    fn test_is_network_mount() {
+        assert!(!is_network_mount(MOUNTS, Path::new("/home/me/Music/a.mp3")));
+        assert!(is_network_mount(MOUNTS, Path::new("/mnt/nas/a.mp3")));
+        assert!(is_network_mount(MOUNTS, Path::new("/home/me/cloud/a.mp3")));
+        assert!(!is_network_mount(MOUNTS, Path::new("/mnt/nas/local/a.mp3")));
+    }
+
+    #[test]
+    fn test_output_error_recognises_missing_target() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("song.mp3");
+        let other = std::io::Error::other("disk on fire");
+
+        assert!(matches!(
+            output_error(&path, "Write error", other),
+            AppError::Io(message) if message == "Write error: disk on fire"
+        ));
+        assert!(matches!(
+            output_error(&path, "Write error", std::io::ErrorKind::NotFound.into()),
+            AppError::Io(message) if message == OUTPUT_DISAPPEARED
+        ));
+
+        let gone = dir.path().join("deleted").join("song.mp3");
+        assert!(matches!(
+            output_error(&gone, "Write error", std::io::Error::other("EIO")),
+            AppError::Io(message) if message == OUTPUT_DISAPPEARED
+        ));
+    }
+
+    #[test]
+    fn test_free_space_margin() {
+        assert_eq!(required_free_space(Some(5_000), 1_000), 6_000);
+        // Unknown size still demands the margin
+        assert_eq!(required_free_space(None, 1_000), 1_000);
+        assert_eq!(required_free_space(Some(u64::MAX), 1_000), u64::MAX);
+
+        assert!(ensure_free_space(6_000, 6_000).is_ok());
+        assert!(matches!(
+            ensure_free_space(5_999, 6_000),
+            Err(AppError::InsufficientSpace {
+                needed: 6_000,
+                available: 5_999
+            })
+        ));
+    }
+
+    #[test]
+    fn test_tuning_for_cloud_folder() {
+        let path = Path::new("/home/me/OneDrive/Music/a.mp3");
+        assert_eq!(WriteTuning::for_path(path, None), WriteTuning::network());
+        assert_eq!(WriteTuning::for_path(path, Some(1024)).buffer_size, 1024);
+    }
+
+    #[tokio::test]
+    async fn test_large_buffer_flushes_everything_on_finish() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("song.mp3");
+        let data: Vec<u8> = (0..3 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+
+        let tuning = WriteTuning {
+            buffer_size: 8 * 1024 * 1024,
+            flush_every: Some(1024 * 1024),
+        };
+        let mut writer = OutputWriter::create(&path, tuning).await.unwrap();
+        for chunk in data.chunks(100_000) {
+            writer.write(chunk).await.unwrap();
+        }
+        writer.finish().await.unwrap();
+
+        assert_eq!(std::fs::read(&path).unwrap(), data);
+    }
+
+    #[tokio::test]
+    async fn test_create_output_file_allows_regular_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("song.mp3");
+        std::fs::write(&path, b"old").unwrap();
+
+        assert!(create_output_file(&path).await.is_ok());
+        assert_eq!(std::fs::read(&path).unwrap(), b"");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_create_output_file_rejects_symlink() {
+        let dir = tempfile::tempdir().unwrap();
+        let victim = dir.path().join("victim.txt");
+        std::fs::write(&victim, b"keep me").unwrap();
+        let link = dir.path().join("song.mp3");
+        std::os::unix::fs::symlink(&victim, &link).unwrap();
+
+        assert!(matches!(
+            create_output_file(&link).await,
+            Err(AppError::SymlinkTarget(_))
+        ));
+        assert_eq!(std::fs::read(&victim).unwrap(), b"keep me");
+    }
+}
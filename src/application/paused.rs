@@ -0,0 +1,73 @@
+use std::path::{Path, PathBuf};
+
+use super::resume::ResumeRecord;
+
+const PAUSED_FILE: &str = "paused.json";
+
+/// Remembers a paused download across restarts of the app
+#[derive(Debug, Clone)]
+pub struct PausedStore {
+    file: PathBuf,
+}
+
+impl PausedStore {
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            file: dir.join(PAUSED_FILE),
+        }
+    }
+
+    /// Store in the user's config directory, if there is one
+    pub fn open_default() -> Option<Self> {
+        crate::utils::config_dir().map(|dir| Self::new(&dir))
+    }
+
+    pub fn save(&self, record: &ResumeRecord) -> std::io::Result<()> {
+        if let Some(dir) = self.file.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&self.file, serde_json::to_vec(record)?)
+    }
+
+    /// The paused download, unless its partial file has gone missing in the meantime
+    pub fn load(&self) -> Option<ResumeRecord> {
+        let json = std::fs::read(&self.file).ok()?;
+        let record: ResumeRecord = serde_json::from_slice(&json).ok()?;
+        ResumeRecord::partial_path(&record.path)
+            .exists()
+            .then_some(record)
+    }
+
+    pub fn clear(&self) {
+        let _ = std::fs::remove_file(&self.file);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paused_store_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = PausedStore::new(&dir.path().join("config"));
+        let record = ResumeRecord {
+            url: "https://cdn.example.com/file.mp3".to_string(),
+            source: Some("https://youtu.be/z0vCwGUZe1I".to_string()),
+            path: dir.path().join("song.mp3"),
+            downloaded_bytes: 5,
+            total: Some(10),
+        };
+
+        assert_eq!(store.load(), None);
+        store.save(&record).unwrap();
+        // The partial file isn't there yet
+        assert_eq!(store.load(), None);
+
+        std::fs::write(ResumeRecord::partial_path(&record.path), b"01234").unwrap();
+        assert_eq!(store.load(), Some(record));
+
+        store.clear();
+        assert_eq!(store.load(), None);
+    }
+}
@@ -0,0 +1,75 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use fs2::FileExt;
+
+const HEADER: &str = "#EXTM3U\n";
+
+/// Add `track` to the M3U8 `playlist`, creating it if missing. The track is listed
+/// relative to the playlist's folder when it's inside it, as players expect.
+pub fn append_to_playlist(
+    playlist: &Path,
+    track: &Path,
+    title: Option<&str>,
+) -> std::io::Result<()> {
+    let entry_path = playlist
+        .parent()
+        .and_then(|dir| track.strip_prefix(dir).ok())
+        .unwrap_or(track);
+
+    let mut entry = String::new();
+    if let Some(title) = title {
+        // Duration isn't known without decoding the file; -1 is the conventional "unknown"
+        entry.push_str(&format!("#EXTINF:-1,{}\n", title.replace('\n', " ")));
+    }
+    entry.push_str(&entry_path.to_string_lossy());
+    entry.push('\n');
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .read(true)
+        .open(playlist)?;
+    // Several downloads may finish at once; keep their lines from interleaving
+    file.lock_exclusive()?;
+    let result = write_entry(&mut file, &entry);
+    let _ = FileExt::unlock(&file);
+    result
+}
+
+fn write_entry(file: &mut File, entry: &str) -> std::io::Result<()> {
+    if file.seek(SeekFrom::End(0))? == 0 {
+        file.write_all(HEADER.as_bytes())?;
+    }
+    file.write_all(entry.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_playlist_after_two_downloads() {
+        let dir = tempfile::tempdir().unwrap();
+        let playlist = dir.path().join("downloads.m3u8");
+
+        append_to_playlist(
+            &playlist,
+            &dir.path().join("Song A.mp3"),
+            Some("Artist - Song A"),
+        )
+        .unwrap();
+        append_to_playlist(&playlist, &dir.path().join("live").join("Song B.mp3"), None).unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&playlist).unwrap(),
+            format!(
+                "#EXTM3U\n#EXTINF:-1,Artist - Song A\nSong A.mp3\n{}\n",
+                Path::new("live").join("Song B.mp3").display()
+            )
+        );
+    }
+}
@@ -0,0 +1,142 @@
+use std::{
+    fmt,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// System command that opens a file or folder with its default application
+#[cfg(target_os = "macos")]
+const OPENER: &str = "open";
+#[cfg(target_os = "windows")]
+const OPENER: &str = "explorer";
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+const OPENER: &str = "xdg-open";
+
+/// Player for `PlayFile`; must be on `PATH`
+const PLAYER: &str = "ffplay";
+
+/// What to do with a file once it has been saved
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum PostDownloadAction {
+    #[default]
+    None,
+    /// Show the file in its folder
+    RevealFolder,
+    /// Open the file with its default application
+    OpenFile,
+    /// Play the file in the background, without a window
+    PlayFile,
+}
+
+impl PostDownloadAction {
+    pub const ALL: [PostDownloadAction; 4] = [
+        PostDownloadAction::None,
+        PostDownloadAction::RevealFolder,
+        PostDownloadAction::OpenFile,
+        PostDownloadAction::PlayFile,
+    ];
+
+    /// Command carrying out the action for `path`, or `None` for no action
+    pub fn command(self, path: &Path) -> Option<Command> {
+        match self {
+            PostDownloadAction::None => None,
+            PostDownloadAction::RevealFolder => Some(reveal_command(path)),
+            PostDownloadAction::OpenFile => Some(open_command(path)),
+            PostDownloadAction::PlayFile => Some(play_command(path)),
+        }
+    }
+
+    /// Start the action without waiting for it to finish
+    pub fn run(self, path: &Path) -> std::io::Result<()> {
+        let Some(mut command) = self.command(path) else {
+            return Ok(());
+        };
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map(|_| ())
+    }
+}
+
+impl fmt::Display for PostDownloadAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PostDownloadAction::None => "Do nothing",
+            PostDownloadAction::RevealFolder => "Show in folder",
+            PostDownloadAction::OpenFile => "Open file",
+            PostDownloadAction::PlayFile => "Play file",
+        })
+    }
+}
+
+fn reveal_command(path: &Path) -> Command {
+    let mut command = Command::new(OPENER);
+    if cfg!(target_os = "macos") {
+        command.arg("-R").arg(path);
+    } else if cfg!(target_os = "windows") {
+        let mut select = std::ffi::OsString::from("/select,");
+        select.push(path);
+        command.arg(select);
+    } else {
+        // xdg-open can't select a file, so open the folder that holds it
+        command.arg(path.parent().unwrap_or(Path::new(".")));
+    }
+    command
+}
+
+fn open_command(path: &Path) -> Command {
+    let mut command = Command::new(OPENER);
+    command.arg(path);
+    command
+}
+
+fn play_command(path: &Path) -> Command {
+    let mut command = Command::new(PLAYER);
+    command
+        .args(["-nodisp", "-autoexit", "-loglevel", "quiet"])
+        .arg(path);
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsStr;
+
+    fn program_and_last_arg(action: PostDownloadAction, path: &Path) -> Option<(String, String)> {
+        let command = action.command(path)?;
+        let last = command.get_args().last().unwrap_or(OsStr::new(""));
+        Some((
+            command.get_program().to_string_lossy().into_owned(),
+            last.to_string_lossy().into_owned(),
+        ))
+    }
+
+    #[test]
+    fn test_action_selects_helper() {
+        let path = Path::new("/music/Artist - Song.mp3");
+
+        assert!(PostDownloadAction::default().command(path).is_none());
+        assert_eq!(
+            program_and_last_arg(PostDownloadAction::OpenFile, path),
+            Some((OPENER.to_string(), path.display().to_string()))
+        );
+        assert_eq!(
+            program_and_last_arg(PostDownloadAction::PlayFile, path),
+            Some((PLAYER.to_string(), path.display().to_string()))
+        );
+
+        let (program, target) =
+            program_and_last_arg(PostDownloadAction::RevealFolder, path).unwrap();
+        assert_eq!(program, OPENER);
+        if cfg!(any(target_os = "macos", target_os = "windows")) {
+            assert!(target.ends_with("Artist - Song.mp3"));
+        } else {
+            assert_eq!(target, "/music");
+        }
+    }
+}
@@ -0,0 +1,259 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+use super::DownloadEvent;
+
+/// Span the transfer rate is averaged over, so it follows changes without jumping per chunk
+const RATE_WINDOW: Duration = Duration::from_secs(5);
+
+/// Overall progress across several downloads
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AggregateProgress {
+    /// Bytes received across all downloads
+    pub downloaded: u64,
+    /// Sum of the sizes that are known
+    pub total: u64,
+    /// Share done of the downloads with a known size, `None` if there are none
+    pub fraction: Option<f32>,
+    /// Downloads left out of `fraction` because their size isn't known
+    pub unknown_totals: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct TaskProgress {
+    downloaded: u64,
+    total: Option<u64>,
+}
+
+/// Sums per-download byte counts into a single overall figure
+#[derive(Debug, Default)]
+pub struct ProgressAggregator {
+    tasks: HashMap<usize, TaskProgress>,
+}
+
+impl ProgressAggregator {
+    /// Fold `event` for download `task` in, returning the new aggregate if it changed
+    pub fn update(&mut self, task: usize, event: &DownloadEvent) -> Option<AggregateProgress> {
+        let progress = self.tasks.entry(task).or_default();
+        match *event {
+            DownloadEvent::Progress { downloaded, total } => {
+                progress.downloaded = downloaded;
+                progress.total = Some(total);
+            }
+            DownloadEvent::Received(downloaded) => progress.downloaded = downloaded,
+            // Whatever arrived is all there is
+            DownloadEvent::Completed { .. } => progress.total = Some(progress.downloaded),
+            DownloadEvent::Preparing(_)
+            | DownloadEvent::Paused(_)
+            | DownloadEvent::Cancelled
+            | DownloadEvent::Failed(_) => return None,
+        }
+        Some(self.aggregate())
+    }
+
+    pub fn aggregate(&self) -> AggregateProgress {
+        let mut aggregate = AggregateProgress {
+            downloaded: 0,
+            total: 0,
+            fraction: None,
+            unknown_totals: 0,
+        };
+        let mut known_downloaded = 0;
+
+        for task in self.tasks.values() {
+            aggregate.downloaded += task.downloaded;
+            match task.total {
+                Some(total) => {
+                    aggregate.total += total;
+                    known_downloaded += task.downloaded;
+                }
+                None => aggregate.unknown_totals += 1,
+            }
+        }
+
+        if aggregate.total > 0 {
+            aggregate.fraction = Some(known_downloaded as f32 / aggregate.total as f32);
+        }
+        aggregate
+    }
+
+    pub fn clear(&mut self) {
+        self.tasks.clear();
+    }
+}
+
+/// Transfer rate of a single download over the last few seconds
+#[derive(Debug, Default)]
+pub struct RateMeter {
+    /// Bytes received so far, by when they had arrived
+    samples: VecDeque<(Instant, u64)>,
+}
+
+impl RateMeter {
+    /// Note that `downloaded` bytes had arrived by `at`
+    pub fn record(&mut self, at: Instant, downloaded: u64) {
+        // Fewer bytes than before means the download started over (e.g. a resume was refused)
+        if self
+            .samples
+            .back()
+            .is_some_and(|&(_, last)| downloaded < last)
+        {
+            self.samples.clear();
+        }
+        self.samples.push_back((at, downloaded));
+
+        // Keep one sample at or beyond the window, so the rate always spans all of it
+        while self
+            .samples
+            .get(1)
+            .is_some_and(|&(second, _)| at.duration_since(second) >= RATE_WINDOW)
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Bytes per second across the window, once two samples lie apart in time
+    pub fn bytes_per_sec(&self) -> Option<f64> {
+        let (&(first_at, first), &(last_at, last)) = (self.samples.front()?, self.samples.back()?);
+        let secs = last_at.duration_since(first_at).as_secs_f64();
+        (secs > 0.0).then(|| (last - first) as f64 / secs)
+    }
+
+    /// Time left until `total` bytes have arrived at the current rate
+    pub fn eta(&self, total: u64) -> Option<Duration> {
+        let rate = self.bytes_per_sec().filter(|rate| *rate > 0.0)?;
+        let &(_, downloaded) = self.samples.back()?;
+        Some(Duration::from_secs_f64(
+            total.saturating_sub(downloaded) as f64 / rate,
+        ))
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feed `chunks` bytes every `every`, starting at `start`
+    fn steady(meter: &mut RateMeter, start: Instant, every: Duration, chunks: u64, size: u64) {
+        for i in 0..=chunks {
+            meter.record(start + every * i as u32, i * size);
+        }
+    }
+
+    #[test]
+    fn test_rate_of_steady_download() {
+        let start = Instant::now();
+        let mut meter = RateMeter::default();
+        assert_eq!(meter.bytes_per_sec(), None);
+        meter.record(start, 0);
+        assert_eq!(meter.bytes_per_sec(), None);
+
+        // 16 KB every 100 ms
+        steady(&mut meter, start, Duration::from_millis(100), 50, 16 * 1024);
+        let rate = meter.bytes_per_sec().unwrap();
+        assert!((rate - 163_840.0).abs() < 1.0, "rate {}", rate);
+
+        let eta = meter.eta(50 * 16 * 1024 + 163_840 * 3).unwrap();
+        assert!((eta.as_secs_f64() - 3.0).abs() < 0.01, "eta {:?}", eta);
+    }
+
+    #[test]
+    fn test_rate_follows_a_slowdown() {
+        let start = Instant::now();
+        let mut meter = RateMeter::default();
+        // 10 s at 100 KB/s, then 10 s at 10 KB/s
+        steady(&mut meter, start, Duration::from_secs(1), 10, 100_000);
+        for i in 1..=10 {
+            meter.record(start + Duration::from_secs(10 + i), 1_000_000 + i * 10_000);
+        }
+
+        let rate = meter.bytes_per_sec().unwrap();
+        assert!((rate - 10_000.0).abs() < 1.0, "rate {}", rate);
+    }
+
+    #[test]
+    fn test_restart_and_stall() {
+        let start = Instant::now();
+        let mut meter = RateMeter::default();
+        steady(&mut meter, start, Duration::from_secs(1), 3, 1_000);
+
+        meter.record(start + Duration::from_secs(4), 0);
+        assert_eq!(meter.bytes_per_sec(), None);
+
+        meter.record(start + Duration::from_secs(5), 0);
+        assert_eq!(meter.bytes_per_sec(), Some(0.0));
+        assert_eq!(meter.eta(1_000), None);
+    }
+
+    fn progress(downloaded: u64, total: u64) -> DownloadEvent {
+        DownloadEvent::Progress { downloaded, total }
+    }
+
+    #[test]
+    fn test_sums_known_totals() {
+        let mut aggregator = ProgressAggregator::default();
+        aggregator.update(1, &progress(50, 100));
+        let aggregate = aggregator.update(2, &progress(25, 300)).unwrap();
+
+        assert_eq!(aggregate.downloaded, 75);
+        assert_eq!(aggregate.total, 400);
+        assert_eq!(aggregate.fraction, Some(75.0 / 400.0));
+        assert_eq!(aggregate.unknown_totals, 0);
+    }
+
+    #[test]
+    fn test_unknown_totals_stay_out_of_the_fraction() {
+        let mut aggregator = ProgressAggregator::default();
+        aggregator.update(1, &progress(50, 100));
+        let aggregate = aggregator
+            .update(2, &DownloadEvent::Received(1_000))
+            .unwrap();
+
+        assert_eq!(aggregate.downloaded, 1_050);
+        assert_eq!(aggregate.total, 100);
+        assert_eq!(aggregate.fraction, Some(0.5));
+        assert_eq!(aggregate.unknown_totals, 1);
+    }
+
+    #[test]
+    fn test_only_unknown_totals() {
+        let mut aggregator = ProgressAggregator::default();
+        let aggregate = aggregator.update(1, &DownloadEvent::Received(10)).unwrap();
+
+        assert_eq!(aggregate.fraction, None);
+        assert_eq!(aggregate.unknown_totals, 1);
+    }
+
+    #[test]
+    fn test_completion_settles_an_unknown_total() {
+        let mut aggregator = ProgressAggregator::default();
+        aggregator.update(1, &DownloadEvent::Received(10));
+        aggregator.update(2, &progress(0, 10));
+        let aggregate = aggregator
+            .update(
+                1,
+                &DownloadEvent::Completed {
+                    path: "song.mp3".into(),
+                    size: 10,
+                    quality: Default::default(),
+                    format: Default::default(),
+                },
+            )
+            .unwrap();
+
+        assert_eq!(aggregate.unknown_totals, 0);
+        assert_eq!(aggregate.fraction, Some(0.5));
+        assert!(aggregator
+            .update(
+                2,
+                &DownloadEvent::Preparing(crate::application::PrepareStage::Init)
+            )
+            .is_none());
+    }
+}
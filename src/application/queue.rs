@@ -0,0 +1,325 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    time::Duration,
+};
+
+use super::report::{BatchReport, ItemOutcome};
+use crate::utils::{extract_playlist_id, extract_video_ids};
+
+/// Consecutive connection failures after which a batch pauses itself
+pub const NETWORK_FAILURE_LIMIT: usize = 3;
+
+/// A single pending download in a batch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueuedItem {
+    pub url: String,
+    /// 1-based position in the batch
+    pub index: usize,
+}
+
+/// Pending downloads, processed one after another
+#[derive(Debug, Clone, Default)]
+pub struct DownloadQueue {
+    items: VecDeque<QueuedItem>,
+    total: usize,
+    number_tracks: bool,
+    paused: bool,
+    /// Links dropped because an earlier one points at the same video
+    duplicates: usize,
+    /// Connection failures in a row, reset by any other outcome or by resuming
+    network_failures: usize,
+    /// Pause after this many connection failures in a row; 0 never pauses
+    network_failure_limit: usize,
+    report: BatchReport,
+}
+
+impl DownloadQueue {
+    /// Build a queue from user input holding one or more whitespace-separated URLs
+    ///
+    /// Links to a video that's already queued (in whatever URL form) are left out.
+    pub fn from_input(input: &str, number_tracks: bool) -> Self {
+        let urls: Vec<&str> = input.split_whitespace().collect();
+        let mut seen = HashSet::new();
+        let unique: Vec<&str> = urls
+            .iter()
+            .copied()
+            // Links without a video ID are kept, to fail with a proper error later
+            .filter(|url| {
+                let ids = extract_video_ids(url);
+                ids.is_empty() || ids.into_iter().filter(|id| seen.insert(id.clone())).count() > 0
+            })
+            .collect();
+
+        let items: VecDeque<QueuedItem> = unique
+            .iter()
+            .enumerate()
+            .map(|(i, url)| QueuedItem {
+                url: url.to_string(),
+                index: i + 1,
+            })
+            .collect();
+
+        Self {
+            total: items.len(),
+            items,
+            number_tracks,
+            paused: false,
+            duplicates: urls.len() - unique.len(),
+            network_failures: 0,
+            network_failure_limit: NETWORK_FAILURE_LIMIT,
+            report: BatchReport::default(),
+        }
+    }
+
+    /// Pause after `limit` consecutive connection failures instead of the default
+    pub fn network_failure_limit(mut self, limit: Option<usize>) -> Self {
+        self.network_failure_limit = limit.unwrap_or(NETWORK_FAILURE_LIMIT);
+        self
+    }
+
+    /// Track whether the item that just ended failed to connect
+    ///
+    /// Returns `true` when that makes too many in a row: the network looks down, so the
+    /// queue pauses itself rather than letting every remaining item time out in turn.
+    pub fn note_connection(&mut self, failed: bool) -> bool {
+        if !failed {
+            self.network_failures = 0;
+            return false;
+        }
+        self.network_failures += 1;
+
+        let tripped = self.network_failure_limit > 0
+            && self.network_failures >= self.network_failure_limit
+            && !self.items.is_empty();
+        if tripped {
+            self.paused = true;
+        }
+        tripped
+    }
+
+    /// Number of links left out of the queue as duplicates
+    pub fn duplicates(&self) -> usize {
+        self.duplicates
+    }
+
+    /// Take the next item to start, or `None` while the queue is paused
+    pub fn next_item(&mut self) -> Option<QueuedItem> {
+        if self.paused {
+            return None;
+        }
+        self.items.pop_front()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Number of items the queue started out with
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// Stop handing out new items; whatever is already running finishes normally
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+        self.network_failures = 0;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn clear(&mut self) {
+        self.items.clear();
+        self.paused = false;
+    }
+
+    /// Note how `item` ended, for the report at the end of the batch
+    pub fn record(&mut self, item: &QueuedItem, outcome: ItemOutcome) {
+        self.report.record(&item.url, outcome);
+    }
+
+    /// Outcomes recorded so far
+    pub fn report(&self) -> &BatchReport {
+        &self.report
+    }
+
+    /// Report over every recorded item, for a batch that took `elapsed`
+    pub fn into_report(self, elapsed: Duration) -> BatchReport {
+        let mut report = self.report;
+        report.finish(elapsed);
+        report
+    }
+
+    /// Playlist ID of the first queued URL that carries one
+    pub fn playlist_id(&self) -> Option<String> {
+        self.items
+            .iter()
+            .find_map(|item| extract_playlist_id(&item.url))
+    }
+
+    /// Playlist of the only item, when the batch is a single link into a playlist
+    pub fn lone_playlist_link(&self) -> Option<String> {
+        match (self.total, self.items.front()) {
+            (1, Some(item)) => extract_playlist_id(&item.url),
+            _ => None,
+        }
+    }
+
+    /// Final filename for an item, prefixed with its track number when enabled for batches
+    pub fn filename_for(&self, item: &QueuedItem, suggested_filename: &str) -> String {
+        if self.number_tracks && self.total > 1 {
+            format!(
+                "{} - {}",
+                index_prefix(item.index, self.total),
+                suggested_filename
+            )
+        } else {
+            suggested_filename.to_string()
+        }
+    }
+}
+
+/// Zero-pad `index` to fit `total`, using at least two digits
+fn index_prefix(index: usize, total: usize) -> String {
+    let width = total.to_string().len().max(2);
+    format!("{:0width$}", index, width = width)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index_prefix_width_follows_total() {
+        assert_eq!(index_prefix(1, 5), "01");
+        assert_eq!(index_prefix(12, 99), "12");
+        assert_eq!(index_prefix(7, 100), "007");
+        assert_eq!(index_prefix(250, 1200), "0250");
+    }
+
+    #[test]
+    fn test_filename_for_batch() {
+        let mut queue = DownloadQueue::from_input("https://youtu.be/a\nhttps://youtu.be/b", true);
+        let first = queue.next_item().unwrap();
+        let second = queue.next_item().unwrap();
+        assert_eq!(queue.filename_for(&first, "Song.mp3"), "01 - Song.mp3");
+        assert_eq!(queue.filename_for(&second, "Other.mp3"), "02 - Other.mp3");
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_filename_for_single_or_disabled() {
+        let mut single = DownloadQueue::from_input("https://youtu.be/a", true);
+        let item = single.next_item().unwrap();
+        assert_eq!(single.filename_for(&item, "Song.mp3"), "Song.mp3");
+
+        let mut disabled = DownloadQueue::from_input("a b", false);
+        let item = disabled.next_item().unwrap();
+        assert_eq!(disabled.filename_for(&item, "Song.mp3"), "Song.mp3");
+    }
+
+    #[test]
+    fn test_paused_queue_starts_nothing_until_resumed() {
+        let mut queue = DownloadQueue::from_input("a b c", false);
+        assert_eq!(queue.next_item().unwrap().url, "a");
+
+        queue.pause();
+        assert!(queue.is_paused());
+        assert_eq!(queue.next_item(), None);
+        assert_eq!(queue.remaining(), 2);
+
+        queue.resume();
+        assert_eq!(queue.next_item().unwrap().url, "b");
+        assert_eq!(queue.next_item().unwrap().url, "c");
+        assert_eq!(queue.next_item(), None);
+    }
+
+    #[test]
+    fn test_consecutive_connection_failures_pause_the_queue() {
+        let mut queue =
+            DownloadQueue::from_input("a b c d e f g", false).network_failure_limit(Some(3));
+
+        // A success in between starts the count over
+        for failed in [true, true, false, true, true] {
+            queue.next_item().unwrap();
+            assert!(!queue.note_connection(failed));
+        }
+        queue.next_item().unwrap();
+        assert!(queue.note_connection(true));
+        assert!(queue.is_paused());
+        assert_eq!(queue.next_item(), None);
+
+        // Resuming gives the network a fresh allowance
+        queue.resume();
+        assert_eq!(queue.next_item().unwrap().url, "g");
+        assert!(!queue.note_connection(true));
+        assert!(!queue.is_paused());
+    }
+
+    #[test]
+    fn test_queue_accumulates_report() {
+        let mut queue = DownloadQueue::from_input("a b", false);
+        let first = queue.next_item().unwrap();
+        queue.record(&first, ItemOutcome::Succeeded { bytes: 10 });
+        let second = queue.next_item().unwrap();
+        queue.record(
+            &second,
+            ItemOutcome::Failed {
+                reason: "gone".to_string(),
+            },
+        );
+
+        let report = queue.into_report(Duration::from_secs(3));
+        assert_eq!((report.succeeded, report.failed), (1, 1));
+        assert_eq!(report.failures[0].url, "b");
+        assert_eq!(report.elapsed_secs, 3.0);
+    }
+
+    #[test]
+    fn test_duplicate_videos_are_queued_once() {
+        let queue = DownloadQueue::from_input(
+            "https://youtu.be/z0vCwGUZe1I \
+             https://www.youtube.com/watch?v=dQw4w9WgXcQ \
+             https://www.youtube.com/watch?v=z0vCwGUZe1I&list=PL9 \
+             not-a-url \
+             https://m.youtube.com/watch?v=dQw4w9WgXcQ&t=42 \
+             z0vCwGUZe1I",
+            false,
+        );
+
+        assert_eq!(queue.duplicates(), 3);
+        assert_eq!(queue.total(), 3);
+        let items: Vec<QueuedItem> = queue.items.iter().cloned().collect();
+        assert_eq!(
+            items,
+            [
+                ("https://youtu.be/z0vCwGUZe1I", 1),
+                ("https://www.youtube.com/watch?v=dQw4w9WgXcQ", 2),
+                ("not-a-url", 3),
+            ]
+            .map(|(url, index)| QueuedItem {
+                url: url.to_string(),
+                index,
+            })
+        );
+    }
+
+    #[test]
+    fn test_playlist_id_from_first_playlist_url() {
+        let queue = DownloadQueue::from_input(
+            "https://youtu.be/z0vCwGUZe1I https://www.youtube.com/watch?v=dQw4w9WgXcQ&list=PL9",
+            false,
+        );
+        assert_eq!(queue.playlist_id(), Some("PL9".to_string()));
+        assert_eq!(DownloadQueue::from_input("a b", false).playlist_id(), None);
+    }
+}
@@ -0,0 +1,116 @@
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::utils::format_bytes;
+
+/// How a single batch item ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ItemOutcome {
+    Succeeded {
+        bytes: u64,
+    },
+    Failed {
+        reason: String,
+    },
+    /// Not attempted, e.g. because the file already exists
+    Skipped {
+        reason: String,
+    },
+}
+
+/// A batch item that didn't make it, and why
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ItemFailure {
+    pub url: String,
+    pub reason: String,
+}
+
+/// Totals over a finished batch run
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct BatchReport {
+    pub succeeded: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    pub total_bytes: u64,
+    pub elapsed_secs: f64,
+    pub failures: Vec<ItemFailure>,
+}
+
+impl BatchReport {
+    pub fn record(&mut self, url: &str, outcome: ItemOutcome) {
+        match outcome {
+            ItemOutcome::Succeeded { bytes } => {
+                self.succeeded += 1;
+                self.total_bytes += bytes;
+            }
+            ItemOutcome::Failed { reason } => {
+                self.failed += 1;
+                self.failures.push(ItemFailure {
+                    url: url.to_string(),
+                    reason,
+                });
+            }
+            ItemOutcome::Skipped { .. } => self.skipped += 1,
+        }
+    }
+
+    pub fn finish(&mut self, elapsed: Duration) {
+        self.elapsed_secs = elapsed.as_secs_f64();
+    }
+
+    /// Plain-text summary: the totals line, then one line per failure
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "{} succeeded, {} failed, {} skipped; {} in {:.1}s",
+            self.succeeded,
+            self.failed,
+            self.skipped,
+            format_bytes(self.total_bytes),
+            self.elapsed_secs
+        );
+        for failure in &self.failures {
+            summary.push_str(&format!("\n  FAILED {}: {}", failure.url, failure.reason));
+        }
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_from_mixed_outcomes() {
+        let mut report = BatchReport::default();
+        report.record("https://youtu.be/a", ItemOutcome::Succeeded { bytes: 1024 });
+        report.record(
+            "https://youtu.be/b",
+            ItemOutcome::Failed {
+                reason: "Download URL not found".to_string(),
+            },
+        );
+        report.record("https://youtu.be/c", ItemOutcome::Succeeded { bytes: 512 });
+        report.record(
+            "https://youtu.be/d",
+            ItemOutcome::Skipped {
+                reason: "already exists".to_string(),
+            },
+        );
+        report.finish(Duration::from_millis(2500));
+
+        assert_eq!(report.succeeded, 2);
+        assert_eq!(report.failed, 1);
+        assert_eq!(report.skipped, 1);
+        assert_eq!(report.total_bytes, 1536);
+        assert_eq!(
+            report.summary(),
+            "2 succeeded, 1 failed, 1 skipped; 1.5 KB in 2.5s\n  \
+             FAILED https://youtu.be/b: Download URL not found"
+        );
+
+        let json: serde_json::Value = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["failures"][0]["url"], "https://youtu.be/b");
+        assert_eq!(json["elapsed_secs"], 2.5);
+    }
+}
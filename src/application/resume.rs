@@ -0,0 +1,137 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// How often the resume record is rewritten while a download is running
+pub const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Appended to the output filename to get the resume record's path
+const RECORD_SUFFIX: &str = ".resume.json";
+/// Appended to the output filename for the file written until the download completes
+const PARTIAL_SUFFIX: &str = ".part";
+
+/// Progress of an unfinished download, kept next to the output file so a later
+/// run can pick up where a crashed one stopped
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResumeRecord {
+    pub url: String,
+    /// YouTube page the download URL was resolved from, to get a fresh one once it expires
+    #[serde(default)]
+    pub source: Option<String>,
+    pub path: PathBuf,
+    pub downloaded_bytes: u64,
+    pub total: Option<u64>,
+}
+
+impl ResumeRecord {
+    pub fn record_path(path: &Path) -> PathBuf {
+        let mut name = path.as_os_str().to_owned();
+        name.push(RECORD_SUFFIX);
+        PathBuf::from(name)
+    }
+
+    /// Where a download into `path` is written until it's complete and moved into place
+    pub fn partial_path(path: &Path) -> PathBuf {
+        let mut name = path.as_os_str().to_owned();
+        name.push(PARTIAL_SUFFIX);
+        PathBuf::from(name)
+    }
+
+    pub async fn save(&self) -> std::io::Result<()> {
+        let json = serde_json::to_vec(self)?;
+        tokio::fs::write(Self::record_path(&self.path), json).await
+    }
+
+    /// Record left behind for `path`, if there is a readable one
+    pub async fn load(path: &Path) -> Option<Self> {
+        let json = tokio::fs::read(Self::record_path(path)).await.ok()?;
+        serde_json::from_slice(&json).ok()
+    }
+
+    pub async fn remove(path: &Path) {
+        let _ = tokio::fs::remove_file(Self::record_path(path)).await;
+    }
+
+    /// Byte offset a download of `url` into `path` can continue from, or 0 to start over
+    pub async fn resume_offset(path: &Path, url: &str) -> u64 {
+        let Some(record) = Self::load(path).await.filter(|r| r.url == url) else {
+            return 0;
+        };
+
+        // Buffered bytes may not have reached the disk before the crash
+        let on_disk = tokio::fs::metadata(Self::partial_path(path))
+            .await
+            .map_or(0, |m| m.len());
+        record.downloaded_bytes.min(on_disk)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(path: &Path, downloaded_bytes: u64) -> ResumeRecord {
+        ResumeRecord {
+            url: "https://cdn.example.com/file.mp3".to_string(),
+            source: None,
+            path: path.to_path_buf(),
+            downloaded_bytes,
+            total: Some(10),
+        }
+    }
+
+    #[test]
+    fn test_record_path_sits_next_to_output() {
+        assert_eq!(
+            ResumeRecord::record_path(Path::new("/music/song.mp3")),
+            PathBuf::from("/music/song.mp3.resume.json")
+        );
+        assert_eq!(
+            ResumeRecord::partial_path(Path::new("/music/song.mp3")),
+            PathBuf::from("/music/song.mp3.part")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_save_load_and_remove() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("song.mp3");
+
+        assert_eq!(ResumeRecord::load(&path).await, None);
+
+        record(&path, 4).save().await.unwrap();
+        assert_eq!(ResumeRecord::load(&path).await, Some(record(&path, 4)));
+
+        ResumeRecord::remove(&path).await;
+        assert_eq!(ResumeRecord::load(&path).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_resume_offset() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("song.mp3");
+        tokio::fs::write(ResumeRecord::partial_path(&path), b"012345")
+            .await
+            .unwrap();
+
+        record(&path, 4).save().await.unwrap();
+        assert_eq!(
+            ResumeRecord::resume_offset(&path, "https://cdn.example.com/file.mp3").await,
+            4
+        );
+        assert_eq!(
+            ResumeRecord::resume_offset(&path, "https://cdn.example.com/other.mp3").await,
+            0
+        );
+
+        // Never trust the record past what actually made it to disk
+        record(&path, 9).save().await.unwrap();
+        assert_eq!(
+            ResumeRecord::resume_offset(&path, "https://cdn.example.com/file.mp3").await,
+            6
+        );
+    }
+}
@@ -0,0 +1,68 @@
+use std::path::{Path, PathBuf};
+
+const SAVE_DIR_FILE: &str = "last-save-dir.json";
+
+/// Remembers where the last download was saved, so the next save dialog starts there
+#[derive(Debug, Clone)]
+pub struct SaveDirStore {
+    file: PathBuf,
+}
+
+impl SaveDirStore {
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            file: dir.join(SAVE_DIR_FILE),
+        }
+    }
+
+    /// Store in the user's config directory, if there is one
+    pub fn open_default() -> Option<Self> {
+        crate::utils::config_dir().map(|dir| Self::new(&dir))
+    }
+
+    pub fn save(&self, save_dir: &Path) -> std::io::Result<()> {
+        if let Some(dir) = self.file.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&self.file, serde_json::to_vec(save_dir)?)
+    }
+
+    /// The remembered directory, unless it has been removed in the meantime
+    pub fn load(&self) -> Option<PathBuf> {
+        let json = std::fs::read(&self.file).ok()?;
+        let dir: PathBuf = serde_json::from_slice(&json).ok()?;
+        dir.is_dir().then_some(dir)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_dir_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SaveDirStore::new(&dir.path().join("config"));
+        let music = dir.path().join("Music");
+        std::fs::create_dir(&music).unwrap();
+
+        assert_eq!(store.load(), None);
+        store.save(&music).unwrap();
+        assert_eq!(store.load(), Some(music));
+    }
+
+    #[test]
+    fn test_deleted_save_dir_is_forgotten() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SaveDirStore::new(dir.path());
+        let music = dir.path().join("Music");
+        std::fs::create_dir(&music).unwrap();
+        store.save(&music).unwrap();
+
+        std::fs::remove_dir(&music).unwrap();
+        assert_eq!(store.load(), None);
+
+        std::fs::write(dir.path().join(SAVE_DIR_FILE), b"not json").unwrap();
+        assert_eq!(store.load(), None);
+    }
+}
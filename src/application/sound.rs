@@ -0,0 +1,67 @@
+use std::io::Write;
+
+use super::DownloadEvent;
+
+/// Audible feedback played when a download finishes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sound {
+    Chime,
+    Error,
+}
+
+impl Sound {
+    /// Pick the sound for a download event, if it ends the download
+    pub fn for_event(event: &DownloadEvent) -> Option<Self> {
+        match event {
+            DownloadEvent::Completed { .. } => Some(Sound::Chime),
+            DownloadEvent::Failed(_) => Some(Sound::Error),
+            DownloadEvent::Preparing(_)
+            | DownloadEvent::Progress { .. }
+            | DownloadEvent::Received(_)
+            | DownloadEvent::Paused(_)
+            | DownloadEvent::Cancelled => None,
+        }
+    }
+
+    /// Ring the system bell, doing nothing when no terminal is attached
+    pub fn play(self) {
+        let bells: &[u8] = match self {
+            Sound::Chime => b"\x07",
+            Sound::Error => b"\x07\x07",
+        };
+
+        let mut stderr = std::io::stderr();
+        let _ = stderr.write_all(bells).and_then(|_| stderr.flush());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::AppError;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_sound_for_event() {
+        assert_eq!(
+            Sound::for_event(&DownloadEvent::Completed {
+                path: PathBuf::from("a.mp3"),
+                size: 10,
+                quality: Default::default(),
+                format: Default::default(),
+            }),
+            Some(Sound::Chime)
+        );
+        assert_eq!(
+            Sound::for_event(&DownloadEvent::Failed(AppError::InvalidInput)),
+            Some(Sound::Error)
+        );
+        assert_eq!(
+            Sound::for_event(&DownloadEvent::Progress {
+                downloaded: 5,
+                total: 10
+            }),
+            None
+        );
+    }
+}
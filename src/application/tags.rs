@@ -0,0 +1,185 @@
+use std::path::Path;
+
+use id3::TagLike;
+
+use super::metadata::{apply_tag, read_existing_tag};
+use crate::domain::{AppError, DownloadPlan, OutputFormat};
+
+/// Metadata to write into a downloaded track
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrackTags {
+    pub title: String,
+    pub artist: Option<String>,
+    /// `artist` is the configured default rather than named by the title, so it only fills
+    /// a missing artist frame
+    pub artist_is_default: bool,
+    pub album: Option<String>,
+}
+
+impl TrackTags {
+    /// Per-track title/artist come from the video title ("Artist - Song"), the album from the plan
+    ///
+    /// `default_artist` is used only when the title doesn't name an artist itself.
+    pub fn from_plan(plan: &DownloadPlan, default_artist: Option<&str>) -> Self {
+        let (artist, title) = match plan.title.split_once(" - ") {
+            Some((artist, song)) if !artist.trim().is_empty() && !song.trim().is_empty() => {
+                (Some(artist.trim().to_string()), song.trim().to_string())
+            }
+            _ => (None, plan.title.trim().to_string()),
+        };
+        let default_artist = default_artist
+            .map(str::trim)
+            .filter(|artist| !artist.is_empty())
+            .map(String::from);
+
+        Self {
+            title,
+            artist_is_default: artist.is_none() && default_artist.is_some(),
+            artist: artist.or(default_artist),
+            album: plan.album.clone(),
+        }
+    }
+
+    /// Whether the file at `path` takes ID3 tags; other containers carry their own metadata
+    pub fn applies_to(path: &Path) -> bool {
+        path.extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case(OutputFormat::Mp3.extension()))
+    }
+
+    /// Set these frames in the file's ID3 tag, keeping the others (e.g. cover art)
+    pub fn write_to(&self, path: &Path) -> Result<(), AppError> {
+        let mut tag = read_existing_tag(path).unwrap_or_default();
+        tag.set_title(&self.title);
+        match &self.artist {
+            // Keep e.g. an artist carried over from the file this one replaced
+            Some(_) if self.artist_is_default && tag.artist().is_some() => {}
+            Some(artist) => tag.set_artist(artist),
+            None => {}
+        }
+        if let Some(album) = &self.album {
+            tag.set_album(album);
+        }
+        apply_tag(path, &tag)
+    }
+
+    /// Short human-readable summary, e.g. "Song by Artist (Album)"
+    pub fn describe(&self) -> String {
+        let mut description = self.title.clone();
+        if let Some(artist) = &self.artist {
+            description.push_str(&format!(" by {}", artist));
+        }
+        if let Some(album) = &self.album {
+            description.push_str(&format!(" ({})", album));
+        }
+        description
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use id3::Tag;
+
+    fn plan(title: &str, album: Option<&str>) -> DownloadPlan {
+        DownloadPlan {
+            title: title.to_string(),
+            download_url: String::new(),
+            suggested_filename: String::new(),
+            album: album.map(String::from),
+            quality: Default::default(),
+            format: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_tags_use_playlist_title_as_album() {
+        let tags = TrackTags::from_plan(&plan("Band - First Song", Some("Greatest Hits")), None);
+        assert_eq!(
+            tags,
+            TrackTags {
+                title: "First Song".to_string(),
+                artist: Some("Band".to_string()),
+                artist_is_default: false,
+                album: Some("Greatest Hits".to_string()),
+            }
+        );
+        assert_eq!(tags.describe(), "First Song by Band (Greatest Hits)");
+    }
+
+    #[test]
+    fn test_tags_without_artist_or_album() {
+        let tags = TrackTags::from_plan(&plan("Just a Title", None), None);
+        assert_eq!(tags.title, "Just a Title");
+        assert_eq!(tags.artist, None);
+        assert_eq!(tags.album, None);
+    }
+
+    #[test]
+    fn test_parsed_artist_wins_over_session_default() {
+        let artist = |title: &str, default: Option<&str>| {
+            TrackTags::from_plan(&plan(title, None), default).artist
+        };
+
+        assert_eq!(
+            artist("Band - Song", Some("Channel")).as_deref(),
+            Some("Band")
+        );
+        assert_eq!(
+            artist("Song", Some(" Channel ")).as_deref(),
+            Some("Channel")
+        );
+        assert_eq!(artist("Song", Some("  ")), None);
+        assert_eq!(artist("Song", None), None);
+    }
+
+    #[test]
+    fn test_only_mp3_files_take_tags() {
+        assert!(TrackTags::applies_to(Path::new("/music/Song.mp3")));
+        assert!(TrackTags::applies_to(Path::new("/music/Song.MP3")));
+        assert!(!TrackTags::applies_to(Path::new("/music/Song.m4a")));
+        assert!(!TrackTags::applies_to(Path::new("/music/Song")));
+    }
+
+    #[test]
+    fn test_write_to_keeps_other_frames() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("song.mp3");
+        std::fs::write(&path, b"audio").unwrap();
+        let mut existing = Tag::new();
+        existing.set_year(1999);
+        apply_tag(&path, &existing).unwrap();
+
+        TrackTags::from_plan(&plan("Song", Some("Album")), Some("Channel"))
+            .write_to(&path)
+            .unwrap();
+
+        let tag = read_existing_tag(&path).unwrap();
+        assert_eq!(tag.title(), Some("Song"));
+        assert_eq!(tag.artist(), Some("Channel"));
+        assert_eq!(tag.album(), Some("Album"));
+        assert_eq!(tag.year(), Some(1999));
+    }
+
+    #[test]
+    fn test_default_artist_never_replaces_existing_one() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("song.mp3");
+        std::fs::write(&path, b"audio").unwrap();
+        let mut existing = Tag::new();
+        existing.set_artist("Carried Over");
+        apply_tag(&path, &existing).unwrap();
+
+        TrackTags::from_plan(&plan("Song", None), Some("Channel"))
+            .write_to(&path)
+            .unwrap();
+        assert_eq!(
+            read_existing_tag(&path).unwrap().artist(),
+            Some("Carried Over")
+        );
+
+        TrackTags::from_plan(&plan("Band - Song", None), Some("Channel"))
+            .write_to(&path)
+            .unwrap();
+        assert_eq!(read_existing_tag(&path).unwrap().artist(), Some("Band"));
+    }
+}
@@ -0,0 +1,178 @@
+use std::io;
+
+use super::output::OutputWriter;
+
+/// How a download written to several places reacts when one of them fails
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TeePolicy {
+    /// Abort as soon as any output fails
+    #[default]
+    FailFast,
+    /// Keep going while at least one output still works
+    BestEffort,
+}
+
+/// Destination the chunks of a download are written to
+pub trait ChunkSink {
+    async fn write(&mut self, chunk: &[u8]) -> io::Result<()>;
+    async fn finish(&mut self) -> io::Result<()>;
+}
+
+impl ChunkSink for OutputWriter {
+    async fn write(&mut self, chunk: &[u8]) -> io::Result<()> {
+        OutputWriter::write(self, chunk).await
+    }
+
+    async fn finish(&mut self) -> io::Result<()> {
+        OutputWriter::finish(self).await
+    }
+}
+
+/// Failure of the output at `index` that ended the whole operation
+#[derive(Debug)]
+pub struct TeeError {
+    pub index: usize,
+    pub error: io::Error,
+}
+
+/// Writes every chunk to all of its sinks
+pub struct Tee<S> {
+    sinks: Vec<Option<S>>,
+    policy: TeePolicy,
+}
+
+impl<S: ChunkSink> Tee<S> {
+    pub fn new(sinks: Vec<S>, policy: TeePolicy) -> Self {
+        Self {
+            sinks: sinks.into_iter().map(Some).collect(),
+            policy,
+        }
+    }
+
+    pub async fn write(&mut self, chunk: &[u8]) -> Result<(), TeeError> {
+        for index in 0..self.sinks.len() {
+            let Some(sink) = &mut self.sinks[index] else {
+                continue;
+            };
+            if let Err(error) = sink.write(chunk).await {
+                self.fail(index, error)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn finish(&mut self) -> Result<(), TeeError> {
+        for index in 0..self.sinks.len() {
+            let Some(sink) = &mut self.sinks[index] else {
+                continue;
+            };
+            if let Err(error) = sink.finish().await {
+                self.fail(index, error)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Drop the output at `index` after `error`, failing if the policy gives up on the whole tee
+    pub fn fail(&mut self, index: usize, error: io::Error) -> Result<(), TeeError> {
+        self.sinks[index] = None;
+
+        if self.policy == TeePolicy::FailFast || self.sinks.iter().all(Option::is_none) {
+            return Err(TeeError { index, error });
+        }
+        Ok(())
+    }
+
+    /// Indices of the outputs still being written
+    pub fn live(&self) -> Vec<usize> {
+        (0..self.sinks.len())
+            .filter(|&index| self.sinks[index].is_some())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    type SharedBuffer = Arc<Mutex<Vec<u8>>>;
+
+    /// In-memory sink that starts failing after `capacity` bytes
+    #[derive(Default)]
+    struct MemorySink {
+        data: SharedBuffer,
+        capacity: Option<usize>,
+    }
+
+    impl ChunkSink for MemorySink {
+        async fn write(&mut self, chunk: &[u8]) -> io::Result<()> {
+            let mut data = self.data.lock().unwrap();
+            if self
+                .capacity
+                .is_some_and(|capacity| data.len() + chunk.len() > capacity)
+            {
+                return Err(io::Error::other("sink full"));
+            }
+            data.extend_from_slice(chunk);
+            Ok(())
+        }
+
+        async fn finish(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn sinks(capacities: &[Option<usize>]) -> (Vec<MemorySink>, Vec<SharedBuffer>) {
+        let sinks: Vec<MemorySink> = capacities
+            .iter()
+            .map(|&capacity| MemorySink {
+                capacity,
+                ..Default::default()
+            })
+            .collect();
+        let data = sinks.iter().map(|sink| sink.data.clone()).collect();
+        (sinks, data)
+    }
+
+    #[tokio::test]
+    async fn test_tee_writes_every_chunk_to_all_sinks() {
+        let (sinks, data) = sinks(&[None, None]);
+        let mut tee = Tee::new(sinks, TeePolicy::FailFast);
+
+        tee.write(b"01234").await.unwrap();
+        tee.write(b"56789").await.unwrap();
+        tee.finish().await.unwrap();
+
+        assert_eq!(*data[0].lock().unwrap(), b"0123456789");
+        assert_eq!(*data[1].lock().unwrap(), b"0123456789");
+        assert_eq!(tee.live(), [0, 1]);
+    }
+
+    #[tokio::test]
+    async fn test_fail_fast_stops_on_first_failing_sink() {
+        let (sinks, _) = sinks(&[None, Some(5)]);
+        let mut tee = Tee::new(sinks, TeePolicy::FailFast);
+
+        tee.write(b"01234").await.unwrap();
+        let error = tee.write(b"56789").await.unwrap_err();
+
+        assert_eq!(error.index, 1);
+    }
+
+    #[tokio::test]
+    async fn test_best_effort_survives_until_all_sinks_fail() {
+        let (sinks, data) = sinks(&[Some(5), Some(10)]);
+        let mut tee = Tee::new(sinks, TeePolicy::BestEffort);
+
+        tee.write(b"01234").await.unwrap();
+        tee.write(b"56789").await.unwrap();
+        assert_eq!(tee.live(), [1]);
+        assert_eq!(*data[1].lock().unwrap(), b"0123456789");
+
+        let error = tee.write(b"!").await.unwrap_err();
+        assert_eq!(error.index, 1);
+        assert!(tee.live().is_empty());
+    }
+}
@@ -0,0 +1,38 @@
+//! Mock backend shared by tests across modules
+
+use crate::api::models::ApiConfig;
+
+pub const AUTH_PAGE: &str = r#"<script>var json = JSON.parse('[[94,118,116,80,77,82,93,66,85,115,110,104,93,123,96,70,57,131,82,95,78,131],1,[14,2,6,10,11,5,0,12,12,5,3,2,4,0,15,11,8,8,11,8,13,16],1,9,3,117]');</script>"#;
+
+/// Mock the auth page, init and convert endpoints on one server
+pub async fn mock_backend(server: &mut mockito::ServerGuard) -> ApiConfig {
+    server
+        .mock("GET", "/")
+        .with_body(AUTH_PAGE)
+        .create_async()
+        .await;
+    server
+        .mock("GET", "/init")
+        .match_query(mockito::Matcher::Any)
+        .with_body(format!(
+            r#"{{"convertURL":"{}/convert?sig=1","error":"0"}}"#,
+            server.url()
+        ))
+        .create_async()
+        .await;
+    server
+        .mock("GET", "/convert")
+        .match_query(mockito::Matcher::Any)
+        .with_body(
+            r#"{"error":0,"progressURL":"","downloadURL":"https://cdn.example.com/file.mp3","redirectURL":"","title":"Artist - Song"}"#,
+        )
+        .create_async()
+        .await;
+
+    ApiConfig {
+        auth_page_url: format!("{}/", server.url()),
+        base_init_url: server.url(),
+        download_base_url: Some(server.url()),
+        ..Default::default()
+    }
+}
@@ -0,0 +1,339 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use futures::{channel::mpsc, stream::BoxStream, StreamExt};
+use mp3lame_encoder::{Bitrate, Builder, Encoder, FlushNoGap, InterleavedPcm, MonoPcm, Quality};
+use symphonia::core::{
+    codecs::audio::AudioDecoderOptions,
+    errors::Error as DecodeError,
+    formats::{probe::Hint, FormatOptions, TrackType},
+    io::MediaSourceStream,
+    meta::MetadataOptions,
+};
+
+use crate::domain::AppError;
+
+/// Bitrates (kbps) the MP3 encoder accepts
+const MP3_BITRATES: &[u32] = &[32, 40, 48, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320];
+
+/// How much newly processed audio warrants another progress report
+const PROGRESS_STEP: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone)]
+pub enum TranscodeEvent {
+    /// Amount of audio processed so far
+    Progress(Duration),
+    Completed(PathBuf),
+    Failed(AppError),
+}
+
+/// Parameters for re-encoding a finished download locally
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TranscodeParams {
+    pub bitrate_kbps: u32,
+}
+
+impl TranscodeParams {
+    /// Parameters for the configured target bitrate, or `None` if transcoding is disabled.
+    /// The target is snapped down to the nearest bitrate the encoder supports.
+    pub fn select(target_kbps: Option<u32>) -> Option<Self> {
+        let target = target_kbps?;
+        let bitrate = MP3_BITRATES
+            .iter()
+            .copied()
+            .filter(|&b| b <= target)
+            .max()
+            .unwrap_or(MP3_BITRATES[0]);
+
+        Some(Self {
+            bitrate_kbps: bitrate,
+        })
+    }
+
+    fn bitrate(&self) -> Bitrate {
+        match self.bitrate_kbps {
+            32 => Bitrate::Kbps32,
+            40 => Bitrate::Kbps40,
+            48 => Bitrate::Kbps48,
+            64 => Bitrate::Kbps64,
+            80 => Bitrate::Kbps80,
+            96 => Bitrate::Kbps96,
+            112 => Bitrate::Kbps112,
+            128 => Bitrate::Kbps128,
+            160 => Bitrate::Kbps160,
+            192 => Bitrate::Kbps192,
+            224 => Bitrate::Kbps224,
+            256 => Bitrate::Kbps256,
+            _ => Bitrate::Kbps320,
+        }
+    }
+
+    fn encoder(&self, channels: usize, sample_rate: u32) -> Result<Encoder, AppError> {
+        let channels = u8::try_from(channels)
+            .ok()
+            .filter(|c| (1..=2).contains(c))
+            .ok_or_else(|| {
+                AppError::Io(format!(
+                    "Transcoding failed: {} channels unsupported",
+                    channels
+                ))
+            })?;
+
+        let mut builder = Builder::new()
+            .ok_or_else(|| AppError::Io("Transcoding failed: encoder unavailable".to_string()))?;
+        builder
+            .set_num_channels(channels)
+            .and_then(|_| builder.set_sample_rate(sample_rate))
+            .and_then(|_| builder.set_brate(self.bitrate()))
+            .and_then(|_| builder.set_quality(Quality::Good))
+            .map_err(encode_error)?;
+        builder.build().map_err(encode_error)
+    }
+}
+
+/// Re-encode `path` in place, reporting progress as the encoder works through it
+pub fn transcode_stream(
+    path: PathBuf,
+    params: TranscodeParams,
+) -> BoxStream<'static, TranscodeEvent> {
+    let (tx, rx) = mpsc::unbounded();
+
+    tokio::spawn(async move {
+        let progress_tx = tx.clone();
+        let source = path.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            transcode(&source, params, |processed| {
+                let _ = progress_tx.unbounded_send(TranscodeEvent::Progress(processed));
+            })
+        })
+        .await
+        .unwrap_or_else(|e| Err(AppError::Io(format!("Transcoding failed: {}", e))));
+
+        let _ = tx.unbounded_send(match result {
+            Ok(()) => TranscodeEvent::Completed(path),
+            Err(e) => TranscodeEvent::Failed(e),
+        });
+    });
+
+    rx.boxed()
+}
+
+fn transcode(
+    path: &Path,
+    params: TranscodeParams,
+    on_progress: impl Fn(Duration),
+) -> Result<(), AppError> {
+    let mut temp_name = path.as_os_str().to_owned();
+    temp_name.push(".transcode.mp3");
+    let temp_path = PathBuf::from(temp_name);
+
+    let result = encode_file(path, &temp_path, params, on_progress);
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+        return result;
+    }
+
+    std::fs::rename(&temp_path, path)
+        .map_err(|e| AppError::Io(format!("Failed to replace file: {}", e)))
+}
+
+/// Decode `input` and write it re-encoded as MP3 to `output`
+fn encode_file(
+    input: &Path,
+    output: &Path,
+    params: TranscodeParams,
+    on_progress: impl Fn(Duration),
+) -> Result<(), AppError> {
+    let source =
+        File::open(input).map_err(|e| AppError::Io(format!("Failed to open file: {}", e)))?;
+    let mut hint = Hint::new();
+    if let Some(ext) = input.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(ext);
+    }
+    let mut format = symphonia::default::get_probe()
+        .probe(
+            &hint,
+            MediaSourceStream::new(Box::new(source), Default::default()),
+            FormatOptions::default(),
+            MetadataOptions::default(),
+        )
+        .map_err(decode_error)?;
+
+    let track = format
+        .default_track(TrackType::Audio)
+        .ok_or_else(|| AppError::Io("Transcoding failed: no audio track".to_string()))?;
+    let track_id = track.id;
+    let codec_params = track
+        .codec_params
+        .as_ref()
+        .and_then(|p| p.audio())
+        .ok_or_else(|| AppError::Io("Transcoding failed: no audio track".to_string()))?;
+    let mut decoder = symphonia::default::get_codecs()
+        .make_audio_decoder(codec_params, &AudioDecoderOptions::default())
+        .map_err(decode_error)?;
+
+    let file =
+        File::create(output).map_err(|e| AppError::Io(format!("Failed to create file: {}", e)))?;
+    let mut writer = BufWriter::new(file);
+    let mut encoder: Option<Encoder> = None;
+    let mut samples: Vec<f32> = Vec::new();
+    let mut mp3 = Vec::new();
+    let mut frames = 0u64;
+    let mut reported = Duration::ZERO;
+
+    while let Some(packet) = format.next_packet().map_err(decode_error)? {
+        if packet.track_id != track_id {
+            continue;
+        }
+        let buffer = match decoder.decode(&packet) {
+            Ok(buffer) => buffer,
+            // A corrupt packet costs a few milliseconds of audio, not the whole file
+            Err(DecodeError::DecodeError(_)) => continue,
+            Err(e) => return Err(decode_error(e)),
+        };
+
+        let sample_rate = buffer.spec().rate();
+        let encoder = match &mut encoder {
+            Some(encoder) => encoder,
+            None => encoder.insert(params.encoder(buffer.spec().channels().count(), sample_rate)?),
+        };
+
+        samples.resize(buffer.samples_interleaved(), 0.0);
+        buffer.copy_to_slice_interleaved(&mut samples);
+        mp3.clear();
+        mp3.reserve(mp3lame_encoder::max_required_buffer_size(buffer.frames()));
+        let encoded = if encoder.num_channels() == 2 {
+            encoder.encode_to_vec(InterleavedPcm(samples.as_slice()), &mut mp3)
+        } else {
+            encoder.encode_to_vec(MonoPcm(samples.as_slice()), &mut mp3)
+        };
+        encoded.map_err(encode_error)?;
+        writer
+            .write_all(&mp3)
+            .map_err(|e| AppError::Io(format!("Failed to write file: {}", e)))?;
+
+        frames += buffer.frames() as u64;
+        let processed = Duration::from_secs_f64(frames as f64 / f64::from(sample_rate.max(1)));
+        if processed >= reported + PROGRESS_STEP {
+            reported = processed;
+            on_progress(processed);
+        }
+    }
+
+    let mut encoder =
+        encoder.ok_or_else(|| AppError::Io("Transcoding failed: no audio decoded".to_string()))?;
+    mp3.clear();
+    mp3.reserve(mp3lame_encoder::max_required_buffer_size(0));
+    encoder
+        .flush_to_vec::<FlushNoGap>(&mut mp3)
+        .map_err(encode_error)?;
+    writer
+        .write_all(&mp3)
+        .and_then(|_| writer.flush())
+        .map_err(|e| AppError::Io(format!("Failed to write file: {}", e)))
+}
+
+fn decode_error(error: DecodeError) -> AppError {
+    AppError::Io(format!("Transcoding failed: {}", error))
+}
+
+fn encode_error(error: impl std::fmt::Display) -> AppError {
+    AppError::Io(format!("Transcoding failed: {}", error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One second of a 440 Hz stereo tone as a 16-bit PCM WAV file
+    fn write_tone(path: &Path) {
+        const RATE: u32 = 44_100;
+        let mut data = Vec::new();
+        for i in 0..RATE {
+            let t = i as f32 / RATE as f32;
+            let sample = ((t * 440.0 * std::f32::consts::TAU).sin() * 8000.0) as i16;
+            data.extend_from_slice(&sample.to_le_bytes());
+            data.extend_from_slice(&sample.to_le_bytes());
+        }
+
+        let mut wav = Vec::new();
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&(36 + data.len() as u32).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes());
+        wav.extend_from_slice(&2u16.to_le_bytes());
+        wav.extend_from_slice(&RATE.to_le_bytes());
+        wav.extend_from_slice(&(RATE * 4).to_le_bytes());
+        wav.extend_from_slice(&4u16.to_le_bytes());
+        wav.extend_from_slice(&16u16.to_le_bytes());
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        wav.extend_from_slice(&data);
+        std::fs::write(path, wav).unwrap();
+    }
+
+    #[test]
+    fn test_select_skipped_when_disabled() {
+        assert_eq!(TranscodeParams::select(None), None);
+    }
+
+    #[test]
+    fn test_select_snaps_to_supported_bitrate() {
+        assert_eq!(
+            TranscodeParams::select(Some(128)).unwrap().bitrate_kbps,
+            128
+        );
+        assert_eq!(
+            TranscodeParams::select(Some(150)).unwrap().bitrate_kbps,
+            128
+        );
+        assert_eq!(
+            TranscodeParams::select(Some(1000)).unwrap().bitrate_kbps,
+            320
+        );
+        assert_eq!(TranscodeParams::select(Some(8)).unwrap().bitrate_kbps, 32);
+    }
+
+    #[tokio::test]
+    async fn test_transcode_reencodes_in_place() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tone.wav");
+        write_tone(&path);
+
+        let params = TranscodeParams { bitrate_kbps: 64 };
+        let events: Vec<_> = transcode_stream(path.clone(), params).collect().await;
+
+        assert!(
+            matches!(events.last(), Some(TranscodeEvent::Completed(p)) if *p == path),
+            "{:?}",
+            events
+        );
+        assert!(events
+            .iter()
+            .any(|e| matches!(e, TranscodeEvent::Progress(d) if *d >= Duration::from_secs(1))));
+        // One second at 64 kbps is roughly 8 KB, a fraction of the 176 KB input
+        let bytes = std::fs::read(&path).unwrap();
+        assert!(bytes.len() < 20_000, "{} bytes", bytes.len());
+        assert_eq!(bytes[0], 0xFF);
+        assert!(!dir.path().join("tone.wav.transcode.mp3").exists());
+    }
+
+    #[tokio::test]
+    async fn test_transcode_failure_keeps_original() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("broken.mp3");
+        std::fs::write(&path, b"not audio at all").unwrap();
+
+        let params = TranscodeParams { bitrate_kbps: 128 };
+        let events: Vec<_> = transcode_stream(path.clone(), params).collect().await;
+
+        assert!(matches!(events.last(), Some(TranscodeEvent::Failed(_))));
+        assert_eq!(std::fs::read(&path).unwrap(), b"not audio at all");
+        assert!(!dir.path().join("broken.mp3.transcode.mp3").exists());
+    }
+}
@@ -0,0 +1,586 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use futures::StreamExt;
+
+use crate::{
+    application::{
+        BatchReport, DownloadCoordinator, DownloadEvent, DownloadQueue, DownloadRequest,
+        EmptyNameFallback, FilenameTemplate, ItemOutcome, QueuedItem, Settings,
+    },
+    domain::{AudioQuality, OutputFormat},
+    utils::format_bytes,
+};
+
+/// Share of a download between two progress lines on stderr, in percent
+const PROGRESS_STEP: u64 = 10;
+/// Bytes between two progress lines when the size isn't known
+const RECEIVED_STEP: u64 = 1024 * 1024;
+
+const USAGE: &str = "usage: simple-mp3-downloader [--url <youtube url> --validate [--quiet]]\n       \
+                     simple-mp3-downloader --cli <youtube url> [--out <dir>] [--format <mp3|m4a|opus|wav>] [--quiet]\n       \
+                     simple-mp3-downloader --url <youtube url>... --out <dir> \
+                     [--quality <source|high|medium|low|NNNkbps>] [--format <mp3|m4a|opus|wav>] \
+                     [--empty-name <id|untitled|timestamp>] [--json] [--quiet]";
+
+/// Headless actions selected on the command line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Resolve the URL (init + convert) and report whether it's downloadable
+    Validate {
+        url: String,
+        /// Print only the verdict
+        quiet: bool,
+    },
+    /// Download every URL into `out`, then print a summary (as JSON with `json`)
+    Download {
+        urls: Vec<String>,
+        out: PathBuf,
+        quality: AudioQuality,
+        format: OutputFormat,
+        /// Name for items whose title leaves nothing usable
+        empty_name: EmptyNameFallback,
+        json: bool,
+        /// Print only the summary, no status or progress lines
+        quiet: bool,
+    },
+}
+
+impl Command {
+    fn quiet(&self) -> bool {
+        match self {
+            Command::Validate { quiet, .. } | Command::Download { quiet, .. } => *quiet,
+        }
+    }
+}
+
+impl Command {
+    /// `Ok(None)` when there are no arguments, i.e. the GUI should start
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Option<Self>, String> {
+        let mut urls = Vec::new();
+        let mut out = None;
+        let mut validate = false;
+        let mut json = false;
+        let mut quiet = false;
+        let mut quality = None;
+        let mut format = None;
+        let mut empty_name = None;
+        // `--cli` downloads into the current directory unless told otherwise
+        let mut headless = false;
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--url" => urls.push(args.next().ok_or("--url needs a value")?),
+                "--cli" => {
+                    urls.push(args.next().ok_or("--cli needs a URL")?);
+                    headless = true;
+                }
+                "--out" => out = Some(PathBuf::from(args.next().ok_or("--out needs a value")?)),
+                "--validate" => validate = true,
+                "--json" => json = true,
+                "--quiet" => quiet = true,
+                "--quality" => {
+                    let value = args.next().ok_or("--quality needs a value")?;
+                    quality = Some(value.parse::<AudioQuality>()?);
+                }
+                "--format" => {
+                    let value = args.next().ok_or("--format needs a value")?;
+                    format = Some(value.parse::<OutputFormat>()?);
+                }
+                "--empty-name" => {
+                    let value = args.next().ok_or("--empty-name needs a value")?;
+                    empty_name = Some(value.parse::<EmptyNameFallback>()?);
+                }
+                other => return Err(format!("unknown argument: {}\n{}", other, USAGE)),
+            }
+        }
+
+        if headless && !validate && out.is_none() {
+            out = Some(PathBuf::from("."));
+        }
+
+        // Download-only flags
+        let download_options =
+            json || quality.is_some() || format.is_some() || empty_name.is_some();
+        match (urls.len(), out, validate) {
+            (0, None, false) if !download_options && !quiet => Ok(None),
+            (1, None, true) if !download_options => Ok(Some(Command::Validate {
+                url: urls.remove(0),
+                quiet,
+            })),
+            (1.., Some(out), false) => Ok(Some(Command::Download {
+                urls,
+                out,
+                quality: quality.unwrap_or_default(),
+                format: format.unwrap_or_default(),
+                empty_name: empty_name.unwrap_or_default(),
+                json,
+                quiet,
+            })),
+            _ => Err(USAGE.to_string()),
+        }
+    }
+}
+
+/// Run `command` to completion, returning the process exit code
+pub fn run(command: Command) -> i32 {
+    let (settings, warning) = Settings::load_default();
+    if let Some(warning) = warning {
+        eprintln!("{}", warning);
+    }
+    run_with(command, &settings)
+}
+
+/// Run `command` with the backend, proxy and headers `settings` configure
+fn run_with(command: Command, settings: &Settings) -> i32 {
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            eprintln!("Failed to start runtime: {}", e);
+            return 1;
+        }
+    };
+
+    // An unusable proxy is an error, never a reason to connect directly
+    let api_client = match settings.api_client() {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        }
+    };
+    let quiet = settings.quiet || command.quiet();
+    let coordinator = DownloadCoordinator::new(api_client)
+        .quiet(quiet)
+        .with_status_hook(|status| eprintln!("{}", status));
+
+    match command {
+        Command::Validate { url, .. } => {
+            let (report, valid) = runtime.block_on(validate(&coordinator, url));
+            println!("{}", report);
+            if valid {
+                0
+            } else {
+                1
+            }
+        }
+        Command::Download {
+            urls,
+            out,
+            quality,
+            format,
+            empty_name,
+            json,
+            ..
+        } => {
+            let coordinator = coordinator.filename_template(
+                FilenameTemplate::new(None, quality, format).empty_name_fallback(empty_name),
+            );
+            let report = runtime.block_on(download_batch(&coordinator, urls, &out, quiet));
+            if json {
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&report).unwrap_or_default()
+                );
+            } else {
+                println!("{}", report.summary());
+            }
+            if report.failed == 0 {
+                0
+            } else {
+                1
+            }
+        }
+    }
+}
+
+/// Download `urls` one after another into `out`, skipping files that already exist;
+/// with `quiet`, failures only show up in the report
+async fn download_batch(
+    coordinator: &DownloadCoordinator,
+    urls: Vec<String>,
+    out: &Path,
+    quiet: bool,
+) -> BatchReport {
+    let started = Instant::now();
+    let mut queue = DownloadQueue::from_input(&urls.join(" "), false);
+    if queue.duplicates() > 0 && !quiet {
+        eprintln!("Skipping {} duplicate link(s)", queue.duplicates());
+    }
+
+    while let Some(item) = queue.next_item() {
+        let outcome = download_item(coordinator, &queue, &item, out, quiet).await;
+        match &outcome {
+            _ if quiet => {}
+            ItemOutcome::Failed { reason } => eprintln!("Failed: {}: {}", item.url, reason),
+            ItemOutcome::Skipped { reason } => eprintln!("Skipped: {}", reason),
+            ItemOutcome::Succeeded { .. } => {}
+        }
+        queue.record(&item, outcome);
+    }
+
+    queue.into_report(started.elapsed())
+}
+
+async fn download_item(
+    coordinator: &DownloadCoordinator,
+    queue: &DownloadQueue,
+    item: &QueuedItem,
+    out: &Path,
+    quiet: bool,
+) -> ItemOutcome {
+    let plan = match coordinator.prepare_download(item.url.clone()).await {
+        Ok(plan) => plan,
+        Err(e) => {
+            return ItemOutcome::Failed {
+                reason: e.to_string(),
+            }
+        }
+    };
+
+    let path = out.join(queue.filename_for(item, &plan.suggested_filename));
+    if path.exists() {
+        return ItemOutcome::Skipped {
+            reason: format!("{} already exists", path.display()),
+        };
+    }
+
+    let mut events =
+        coordinator.download(DownloadRequest::from_plan(plan, path).source(item.url.clone()));
+    let mut reported = None;
+    while let Some(event) = events.next().await {
+        if let Some(line) = progress_line(&event, &mut reported).filter(|_| !quiet) {
+            eprintln!("{}", line);
+        }
+        match event {
+            DownloadEvent::Completed { path, .. } => {
+                let bytes = tokio::fs::metadata(&path).await.map_or(0, |m| m.len());
+                return ItemOutcome::Succeeded { bytes };
+            }
+            DownloadEvent::Failed(e) => {
+                return ItemOutcome::Failed {
+                    reason: e.to_string(),
+                }
+            }
+            _ => {}
+        }
+    }
+    ItemOutcome::Failed {
+        reason: "Download ended unexpectedly".to_string(),
+    }
+}
+
+/// Progress line for `event`, once it has moved a step past the `reported` one
+fn progress_line(event: &DownloadEvent, reported: &mut Option<u64>) -> Option<String> {
+    let (step, line) = match *event {
+        DownloadEvent::Progress { downloaded, total } => {
+            let percent = downloaded * 100 / total;
+            (
+                percent / PROGRESS_STEP,
+                format!(
+                    "Downloading: {}% ({} of {})",
+                    percent,
+                    format_bytes(downloaded),
+                    format_bytes(total)
+                ),
+            )
+        }
+        DownloadEvent::Received(downloaded) => (
+            downloaded / RECEIVED_STEP,
+            format!("Downloaded {}", format_bytes(downloaded)),
+        ),
+        _ => return None,
+    };
+    if reported.is_some_and(|reported| step <= reported) {
+        return None;
+    }
+    *reported = Some(step);
+    Some(line)
+}
+
+/// One-line verdict for `url`, and whether a download URL was obtained
+async fn validate(coordinator: &DownloadCoordinator, url: String) -> (String, bool) {
+    match coordinator.fetch_metadata(url).await {
+        Ok((plan, size)) => {
+            let size = size.map_or_else(|| "size unknown".to_string(), format_bytes);
+            (format!("OK: {} ({})", plan.title, size), true)
+        }
+        Err(e) => (format!("UNAVAILABLE: {}", e), false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{api::ApiClient, application::test_support::mock_backend};
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Command::parse(args(&[])), Ok(None));
+        assert_eq!(
+            Command::parse(args(&["--url", "https://youtu.be/abc", "--validate"])),
+            Ok(Some(Command::Validate {
+                url: "https://youtu.be/abc".to_string(),
+                quiet: false,
+            }))
+        );
+        assert_eq!(
+            Command::parse(args(&[
+                "--url", "a", "--url", "b", "--out", "music", "--json"
+            ])),
+            Ok(Some(Command::Download {
+                urls: vec!["a".to_string(), "b".to_string()],
+                out: PathBuf::from("music"),
+                quality: AudioQuality::Source,
+                format: OutputFormat::Mp3,
+                empty_name: EmptyNameFallback::VideoId,
+                json: true,
+                quiet: false,
+            }))
+        );
+        assert!(Command::parse(args(&["--validate"])).is_err());
+        assert!(Command::parse(args(&["--out", "music"])).is_err());
+        assert!(Command::parse(args(&["--url", "a", "--url", "b", "--validate"])).is_err());
+        assert!(Command::parse(args(&["--url"])).is_err());
+        assert!(Command::parse(args(&["--bogus"])).is_err());
+    }
+
+    #[test]
+    fn test_parse_cli_shorthand() {
+        let download = |out: &str| {
+            Ok(Some(Command::Download {
+                urls: vec!["https://youtu.be/abc".to_string()],
+                out: PathBuf::from(out),
+                quality: AudioQuality::Source,
+                format: OutputFormat::Mp3,
+                empty_name: EmptyNameFallback::VideoId,
+                json: false,
+                quiet: false,
+            }))
+        };
+        assert_eq!(
+            Command::parse(args(&["--cli", "https://youtu.be/abc"])),
+            download(".")
+        );
+        assert_eq!(
+            Command::parse(args(&["--cli", "https://youtu.be/abc", "--out", "music"])),
+            download("music")
+        );
+        assert!(Command::parse(args(&["--cli"])).is_err());
+    }
+
+    #[test]
+    fn test_parse_quiet() {
+        assert!(matches!(
+            Command::parse(args(&["--cli", "https://youtu.be/abc", "--quiet"])),
+            Ok(Some(Command::Download { quiet: true, .. }))
+        ));
+        assert_eq!(
+            Command::parse(args(&["--url", "a", "--validate", "--quiet"])),
+            Ok(Some(Command::Validate {
+                url: "a".to_string(),
+                quiet: true,
+            }))
+        );
+        // Nothing to be quiet about in the GUI
+        assert!(Command::parse(args(&["--quiet"])).is_err());
+    }
+
+    #[test]
+    fn test_progress_lines_are_stepped() {
+        let mut reported = None;
+        let lines: Vec<String> = [0, 5, 10, 35, 38, 40, 100]
+            .into_iter()
+            .filter_map(|downloaded| {
+                let event = DownloadEvent::Progress {
+                    downloaded,
+                    total: 100,
+                };
+                progress_line(&event, &mut reported)
+            })
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                "Downloading: 0% (0 B of 100 B)",
+                "Downloading: 10% (10 B of 100 B)",
+                "Downloading: 35% (35 B of 100 B)",
+                "Downloading: 40% (40 B of 100 B)",
+                "Downloading: 100% (100 B of 100 B)",
+            ]
+        );
+
+        let mut reported = None;
+        let received = |bytes, reported: &mut Option<u64>| {
+            progress_line(&DownloadEvent::Received(bytes), reported)
+        };
+        assert_eq!(
+            received(10, &mut reported).as_deref(),
+            Some("Downloaded 10 B")
+        );
+        assert_eq!(received(RECEIVED_STEP - 1, &mut reported), None);
+        assert_eq!(
+            received(RECEIVED_STEP, &mut reported).as_deref(),
+            Some("Downloaded 1.0 MB")
+        );
+    }
+
+    #[test]
+    fn test_parse_quality_and_format() {
+        let parsed = Command::parse(args(&[
+            "--url",
+            "a",
+            "--out",
+            "music",
+            "--quality",
+            "high",
+            "--format",
+            "MP3",
+            "--empty-name",
+            "untitled",
+        ]));
+        assert!(matches!(
+            parsed,
+            Ok(Some(Command::Download {
+                quality: AudioQuality::Kbps(320),
+                format: OutputFormat::Mp3,
+                empty_name: EmptyNameFallback::Untitled,
+                ..
+            }))
+        ));
+        let parsed = Command::parse(args(&[
+            "--url",
+            "a",
+            "--out",
+            "music",
+            "--quality",
+            "192kbps",
+        ]));
+        assert!(matches!(
+            parsed,
+            Ok(Some(Command::Download {
+                quality: AudioQuality::Kbps(192),
+                ..
+            }))
+        ));
+
+        let invalid = Command::parse(args(&["--url", "a", "--out", "music", "--quality", "best"]));
+        assert!(invalid.unwrap_err().contains("unknown quality 'best'"));
+        let invalid = Command::parse(args(&["--url", "a", "--out", "music", "--format", "ogg"]));
+        assert!(invalid.unwrap_err().contains("unknown format 'ogg'"));
+        let parsed = Command::parse(args(&["--url", "a", "--out", "music", "--format", "opus"]));
+        assert!(matches!(
+            parsed,
+            Ok(Some(Command::Download {
+                format: OutputFormat::Opus,
+                ..
+            }))
+        ));
+        // The backend is asked for the bitrate, whatever the format
+        let parsed = Command::parse(args(&[
+            "--url",
+            "a",
+            "--out",
+            "music",
+            "--format",
+            "m4a",
+            "--quality",
+            "low",
+        ]));
+        assert!(matches!(
+            parsed,
+            Ok(Some(Command::Download {
+                quality: AudioQuality::Kbps(_),
+                format: OutputFormat::M4a,
+                ..
+            }))
+        ));
+        // Quality only applies to downloads
+        assert!(Command::parse(args(&["--url", "a", "--validate", "--quality", "low"])).is_err());
+    }
+
+    #[test]
+    fn test_unusable_proxy_fails_the_run() {
+        let mut settings = Settings::default();
+        settings.api.proxy = Some("ftp://proxy.local".to_string());
+
+        let command = Command::Validate {
+            url: "https://youtu.be/z0vCwGUZe1I".to_string(),
+            quiet: true,
+        };
+        assert_eq!(run_with(command, &settings), 1);
+    }
+
+    #[tokio::test]
+    async fn test_validate_reachable_video() {
+        let mut server = mockito::Server::new_async().await;
+        let config = mock_backend(&mut server).await;
+        server
+            .mock("HEAD", "/file.mp3")
+            .with_header("content-length", "2048")
+            .create_async()
+            .await;
+
+        let coordinator = DownloadCoordinator::new(ApiClient::new(config)).quiet(true);
+        let (report, valid) = validate(&coordinator, "https://youtu.be/z0vCwGUZe1I".into()).await;
+
+        assert!(valid);
+        assert_eq!(report, "OK: Artist - Song (2.0 KB)");
+    }
+
+    #[tokio::test]
+    async fn test_download_batch_reports_each_outcome() {
+        let mut server = mockito::Server::new_async().await;
+        let config = mock_backend(&mut server).await;
+        server
+            .mock("GET", "/file.mp3")
+            .with_body("0123456789")
+            .create_async()
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let coordinator = DownloadCoordinator::new(ApiClient::new(config)).quiet(true);
+        let urls = vec![
+            "https://youtu.be/z0vCwGUZe1I".to_string(),
+            // Another video, but the mock backend gives it the same title
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ".to_string(),
+            "not-a-url".to_string(),
+        ];
+        let report = download_batch(&coordinator, urls, dir.path(), true).await;
+
+        assert_eq!(report.succeeded, 1);
+        assert_eq!(report.skipped, 1);
+        assert_eq!(report.failed, 1);
+        assert_eq!(report.total_bytes, 10);
+        assert_eq!(report.failures[0].url, "not-a-url");
+        assert!(dir.path().join("Artist - Song.mp3").exists());
+    }
+
+    #[tokio::test]
+    async fn test_validate_unavailable_video() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/convert")
+            .match_query(mockito::Matcher::Any)
+            .with_body(r#"{"error":0,"progressURL":"","downloadURL":"","redirectURL":""}"#)
+            // The first answer plus every re-convert while it looks queued
+            .expect(6)
+            .create_async()
+            .await;
+        let mut config = mock_backend(&mut server).await;
+        config.poll_interval = std::time::Duration::from_millis(1);
+
+        let coordinator = DownloadCoordinator::new(ApiClient::new(config)).quiet(true);
+        let (report, valid) = validate(&coordinator, "https://youtu.be/z0vCwGUZe1I".into()).await;
+
+        assert!(!valid);
+        assert_eq!(report, "UNAVAILABLE: API error: Download URL not found");
+    }
+}
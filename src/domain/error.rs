@@ -1,5 +1,7 @@
 use thiserror::Error;
 
+use crate::utils::format_bytes;
+
 #[derive(Debug, Clone, Error)]
 pub enum AppError {
     #[error("Invalid YouTube URL or video ID")]
@@ -8,6 +10,31 @@ pub enum AppError {
     #[error("API error: {0}")]
     Api(String),
 
+    #[error("Network error: {0}")]
+    Network(String),
+
     #[error("I/O error: {0}")]
     Io(String),
+
+    #[error("Unexpected content: {0}")]
+    InvalidContent(String),
+
+    #[error("Refusing to write through symlink: {0}")]
+    SymlinkTarget(String),
+
+    #[error(
+        "Not enough disk space: {} needed, {} free",
+        format_bytes(*needed),
+        format_bytes(*available)
+    )]
+    InsufficientSpace { needed: u64, available: u64 },
+
+    #[error("Incomplete download: expected {expected} bytes, got {got}")]
+    IncompleteDownload { expected: u64, got: u64 },
+}
+
+impl AppError {
+    pub fn is_network(&self) -> bool {
+        matches!(self, AppError::Network(_))
+    }
 }
@@ -2,4 +2,4 @@ pub mod error;
 pub mod model;
 
 pub use error::AppError;
-pub use model::{DownloadPhase, DownloadPlan};
+pub use model::{AudioQuality, DownloadPhase, DownloadPlan, OutputFormat};
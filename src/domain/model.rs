@@ -1,8 +1,17 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
 #[derive(Debug, Clone)]
 pub struct DownloadPlan {
     pub title: String,
     pub download_url: String,
     pub suggested_filename: String,
+    /// Album to tag the track with, e.g. the title of the playlist it came from
+    pub album: Option<String>,
+    /// Quality the file was requested in
+    pub quality: AudioQuality,
+    pub format: OutputFormat,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -14,3 +23,130 @@ pub enum DownloadPhase {
     Completed,
     Failed,
 }
+
+/// Bitrate the saved file ends up at
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum AudioQuality {
+    /// Whatever the backend delivered, untouched
+    #[default]
+    Source,
+    /// Re-encoded locally to this bitrate
+    Kbps(u32),
+}
+
+impl std::str::FromStr for AudioQuality {
+    type Err = String;
+
+    /// `source`, a preset (`high`, `medium`, `low`) or a bitrate like `192` / `192kbps`
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim().to_ascii_lowercase();
+        match value.as_str() {
+            "source" => Ok(AudioQuality::Source),
+            "high" => Ok(AudioQuality::Kbps(320)),
+            "medium" => Ok(AudioQuality::Kbps(192)),
+            "low" => Ok(AudioQuality::Kbps(128)),
+            other => other
+                .strip_suffix("kbps")
+                .unwrap_or(other)
+                .parse()
+                .ok()
+                .filter(|&kbps| kbps > 0)
+                .map(AudioQuality::Kbps)
+                .ok_or_else(|| {
+                    format!(
+                        "unknown quality '{}' (expected source, high, medium, low or e.g. 192kbps)",
+                        value
+                    )
+                }),
+        }
+    }
+}
+
+impl AudioQuality {
+    /// Target bitrate, `None` for the source quality
+    pub fn kbps(self) -> Option<u32> {
+        match self {
+            AudioQuality::Source => None,
+            AudioQuality::Kbps(kbps) => Some(kbps),
+        }
+    }
+
+    pub fn label(self) -> String {
+        match self {
+            AudioQuality::Source => "source".to_string(),
+            AudioQuality::Kbps(kbps) => format!("{}kbps", kbps),
+        }
+    }
+}
+
+impl fmt::Display for AudioQuality {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.label())
+    }
+}
+
+/// Container/codec of the saved file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum OutputFormat {
+    #[default]
+    Mp3,
+    M4a,
+    Opus,
+    Wav,
+}
+
+impl OutputFormat {
+    pub const ALL: [OutputFormat; 4] = [
+        OutputFormat::Mp3,
+        OutputFormat::M4a,
+        OutputFormat::Opus,
+        OutputFormat::Wav,
+    ];
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Mp3 => "mp3",
+            OutputFormat::M4a => "m4a",
+            OutputFormat::Opus => "opus",
+            OutputFormat::Wav => "wav",
+        }
+    }
+
+    /// Name of the file type, as shown in the save dialog's filter
+    pub fn description(self) -> &'static str {
+        match self {
+            OutputFormat::Mp3 => "MP3 audio",
+            OutputFormat::M4a => "MPEG-4 audio",
+            OutputFormat::Opus => "Opus audio",
+            OutputFormat::Wav => "WAV audio",
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            OutputFormat::Mp3 => "MP3",
+            OutputFormat::M4a => "M4A",
+            OutputFormat::Opus => "Opus",
+            OutputFormat::Wav => "WAV",
+        })
+    }
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "mp3" => Ok(OutputFormat::Mp3),
+            "m4a" => Ok(OutputFormat::M4a),
+            "opus" => Ok(OutputFormat::Opus),
+            "wav" => Ok(OutputFormat::Wav),
+            _ => Err(format!(
+                "unknown format '{}' (expected mp3, m4a, opus or wav)",
+                value
+            )),
+        }
+    }
+}
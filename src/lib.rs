@@ -0,0 +1,24 @@
+//! Download YouTube audio through a y2mate-style conversion backend
+//!
+//! The GUI is just one front end; everything it drives lives here:
+//!
+//! - [`ApiClient`] and [`ApiConfig`] talk to the backend: conversion, download links,
+//!   titles and thumbnails.
+//! - [`DownloadCoordinator`] turns a YouTube URL into a [`DownloadPlan`] and a plan into a
+//!   saved file, reporting each step as a [`DownloadEvent`].
+//! - [`AppError`] is what every fallible step above ends in.
+//! - [`utils`] holds the URL, filename and formatting helpers they share, e.g.
+//!   [`utils::extract_video_id`].
+//!
+//! The `api`, `application`, `domain` and `utils` modules are public for the less common
+//! pieces (batch queues, tags, resume records); the re-exports below are the stable core.
+
+pub mod api;
+pub mod application;
+pub mod cli;
+pub mod domain;
+pub mod utils;
+
+pub use api::{models::ApiConfig, ApiClient};
+pub use application::{DownloadCoordinator, DownloadEvent};
+pub use domain::{AppError, DownloadPlan};
@@ -1,13 +1,19 @@
-mod api;
 mod app;
-mod application;
-mod domain;
 mod ui;
-mod utils;
 
 use iced::window;
+use simple_mp3_downloader::{api, application, cli, domain, utils};
 
 fn main() -> iced::Result {
+    match cli::Command::parse(std::env::args().skip(1)) {
+        Ok(Some(command)) => std::process::exit(cli::run(command)),
+        Ok(None) => {}
+        Err(usage) => {
+            eprintln!("{}", usage);
+            std::process::exit(2);
+        }
+    }
+
     let icon_data = include_bytes!("../assets/icon.png");
 
     let icon = match image::load_from_memory(icon_data) {
@@ -20,9 +26,11 @@ fn main() -> iced::Result {
     };
 
     iced::application(app::DownloadApp::default, app::update, app::view)
+        .subscription(app::subscription)
         .title("Simple MP3 Downloader")
         .window(window::Settings {
             icon,
+            size: ui::Layout::Full.window_size(),
             ..Default::default()
         })
         .run()
@@ -1,14 +1,73 @@
+use std::path::PathBuf;
+
 use iced::{
-    widget::{button, column, progress_bar, text, text_input, Space},
-    Element, Length,
+    widget::{
+        button, checkbox, column, pick_list, progress_bar, row, scrollable, text, text_input, Space,
+    },
+    Element, Length, Size,
+};
+
+use crate::{
+    application::PostDownloadAction,
+    domain::{AudioQuality, OutputFormat},
 };
 
+/// Bitrates offered for MP3 downloads
+const QUALITIES: [AudioQuality; 5] = [
+    AudioQuality::Source,
+    AudioQuality::Kbps(128),
+    AudioQuality::Kbps(192),
+    AudioQuality::Kbps(256),
+    AudioQuality::Kbps(320),
+];
+
+/// How far the bar of a download of unknown size moves per tick
+const PULSE_STEP: f32 = 0.25;
+
 /// Main view state
 pub struct DownloadView {
     pub youtube_url: String,
     pub status_message: String,
     pub is_downloading: bool,
     pub download_progress: f32,
+    /// The download's size isn't known, so the bar sweeps instead of showing a share
+    pub progress_indeterminate: bool,
+    /// Progress across the whole batch, shown only for multi-item downloads
+    pub overall_progress: Option<f32>,
+    pub compact: bool,
+    pub post_download: PostDownloadAction,
+    pub format: OutputFormat,
+    /// Bitrate to ask for; only MP3 offers a choice
+    pub quality: AudioQuality,
+    /// Batch items still waiting to start
+    pub queued: usize,
+    pub queue_paused: bool,
+    /// Title of the video currently in the URL field, once looked up
+    pub preview_title: Option<String>,
+    /// Channel that uploaded the previewed video, when known
+    pub preview_channel: Option<String>,
+    /// Filename the previewed video would be saved under with the current settings
+    pub preview_filename: Option<String>,
+    /// Whether the current lookup can be cancelled right now
+    pub can_cancel: bool,
+    /// Whether the running download can be paused right now
+    pub can_pause: bool,
+    /// File name of a paused download that can be resumed
+    pub paused_download: Option<String>,
+    /// Save into `output_dir` without asking
+    pub use_output_dir: bool,
+    pub output_dir: Option<PathBuf>,
+    /// Proxy field as typed, applied only once submitted
+    pub proxy: String,
+    /// Past downloads, newest first
+    pub history: Vec<HistoryRow>,
+}
+
+/// A past download as listed in the view
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryRow {
+    pub title: String,
+    pub url: String,
 }
 
 impl Default for DownloadView {
@@ -18,6 +77,24 @@ impl Default for DownloadView {
             status_message: "Enter a youtube video url to download".to_string(),
             is_downloading: false,
             download_progress: 0.0,
+            progress_indeterminate: false,
+            overall_progress: None,
+            compact: false,
+            post_download: PostDownloadAction::default(),
+            format: OutputFormat::default(),
+            quality: AudioQuality::default(),
+            queued: 0,
+            queue_paused: false,
+            preview_title: None,
+            preview_channel: None,
+            preview_filename: None,
+            can_cancel: false,
+            can_pause: false,
+            paused_download: None,
+            use_output_dir: false,
+            output_dir: None,
+            proxy: String::new(),
+            history: Vec::new(),
         }
     }
 }
@@ -26,6 +103,41 @@ impl Default for DownloadView {
 pub enum DownloadMessage {
     YoutubeUrlChanged(String),
     DownloadPressed,
+    CompactToggled(bool),
+    PostDownloadSelected(PostDownloadAction),
+    FormatChanged(OutputFormat),
+    BitrateChanged(AudioQuality),
+    ExportSettingsPressed,
+    PauseQueuePressed,
+    ResumeQueuePressed,
+    PastePressed,
+    CancelPressed,
+    PauseDownloadPressed,
+    ResumeDownloadPressed,
+    /// Switch between asking where to save and saving into the default folder
+    UseOutputDirToggled(bool),
+    ChooseOutputDirPressed,
+    /// Put the URL of a past download back into the input field
+    RedownloadPressed(String),
+    ProxyChanged(String),
+    /// Use the proxy in the field from now on, or none if it's empty
+    ProxySubmitted,
+}
+
+/// Which layout the view is built with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    Full,
+    Compact,
+}
+
+impl Layout {
+    pub fn window_size(self) -> Size {
+        match self {
+            Layout::Full => Size::new(1024.0, 768.0),
+            Layout::Compact => Size::new(480.0, 60.0),
+        }
+    }
 }
 
 impl DownloadView {
@@ -33,14 +145,81 @@ impl DownloadView {
         match message {
             DownloadMessage::YoutubeUrlChanged(id) => {
                 self.youtube_url = id;
+                self.preview_title = None;
+                self.preview_channel = None;
+                self.preview_filename = None;
             }
             DownloadMessage::DownloadPressed => {
                 // Will be handled by the app
             }
+            DownloadMessage::CompactToggled(compact) => {
+                self.compact = compact;
+            }
+            DownloadMessage::PostDownloadSelected(action) => {
+                self.post_download = action;
+            }
+            DownloadMessage::FormatChanged(format) => {
+                self.format = format;
+                // The backend only re-encodes to a chosen bitrate for MP3
+                if format != OutputFormat::Mp3 {
+                    self.quality = AudioQuality::Source;
+                }
+            }
+            DownloadMessage::BitrateChanged(quality) => {
+                self.quality = quality;
+            }
+            DownloadMessage::UseOutputDirToggled(enabled) => {
+                self.use_output_dir = enabled;
+            }
+            DownloadMessage::ProxyChanged(proxy) => {
+                self.proxy = proxy;
+            }
+            DownloadMessage::ExportSettingsPressed
+            | DownloadMessage::PauseQueuePressed
+            | DownloadMessage::ResumeQueuePressed
+            | DownloadMessage::PastePressed
+            | DownloadMessage::CancelPressed
+            | DownloadMessage::PauseDownloadPressed
+            | DownloadMessage::ResumeDownloadPressed
+            | DownloadMessage::ChooseOutputDirPressed
+            | DownloadMessage::RedownloadPressed(_)
+            | DownloadMessage::ProxySubmitted => {
+                // Will be handled by the app
+            }
+        }
+    }
+
+    /// Move the bar of a download of unknown size along, so it doesn't look stuck
+    pub fn pulse(&mut self) {
+        if self.progress_indeterminate {
+            self.download_progress = (self.download_progress + PULSE_STEP) % (1.0 + PULSE_STEP);
+        }
+    }
+
+    pub fn layout(&self) -> Layout {
+        if self.compact {
+            Layout::Compact
+        } else {
+            Layout::Full
         }
     }
 
     pub fn view(&self) -> Element<'_, DownloadMessage> {
+        match self.layout() {
+            Layout::Full => self.full_view(),
+            Layout::Compact => self.compact_view(),
+        }
+    }
+
+    fn download_message(&self) -> Option<DownloadMessage> {
+        if !self.is_downloading {
+            Some(DownloadMessage::DownloadPressed)
+        } else {
+            None
+        }
+    }
+
+    fn full_view(&self) -> Element<'_, DownloadMessage> {
         let progress_bar = if self.is_downloading {
             Some(progress_bar(0.0..=1.0, self.download_progress))
         } else {
@@ -51,13 +230,32 @@ impl DownloadView {
             text("MP3 Downloader").size(32),
             Space::new().height(Length::Fixed(20.0)),
             text("YouTube URL:").size(16),
-            text_input("Enter YouTube URL...", &self.youtube_url)
-                .on_input(DownloadMessage::YoutubeUrlChanged)
-                .padding(10),
-            Space::new().height(Length::Fixed(10.0)),
-            text(&self.status_message).size(14),
+            row![
+                text_input("Enter YouTube URL...", &self.youtube_url)
+                    .on_input(DownloadMessage::YoutubeUrlChanged)
+                    .on_submit_maybe(self.download_message())
+                    .padding(10),
+                button("Paste")
+                    .on_press(DownloadMessage::PastePressed)
+                    .padding(10),
+            ]
+            .spacing(10),
         ];
 
+        if let Some(title) = &self.preview_title {
+            content = content.push(text(title).size(14));
+        }
+        if let Some(channel) = &self.preview_channel {
+            content = content.push(text(format!("by {}", channel)).size(12));
+        }
+        if let Some(filename) = &self.preview_filename {
+            content = content.push(text(format!("Will be saved as: {}", filename)).size(12));
+        }
+
+        content = content
+            .push(Space::new().height(Length::Fixed(10.0)))
+            .push(text(&self.status_message).size(14));
+
         // Add progress bar if downloading
         if let Some(pb) = progress_bar {
             content = content
@@ -65,16 +263,219 @@ impl DownloadView {
                 .push(pb);
         }
 
-        content = content.push(Space::new().height(Length::Fixed(20.0))).push(
-            button("Download MP3")
-                .on_press_maybe(if !self.is_downloading {
-                    Some(DownloadMessage::DownloadPressed)
-                } else {
-                    None
-                })
-                .padding([10, 20]),
+        if let Some(overall) = self.overall_progress {
+            content = content
+                .push(text(format!("Overall: {:.0}%", overall * 100.0)).size(12))
+                .push(iced::widget::progress_bar(0.0..=1.0, overall));
+        }
+
+        content = content.push(self.output_picker());
+
+        let mut actions = row![button(text(format!("Download {}", self.format)))
+            .on_press_maybe(self.download_message())
+            .padding([10, 20])]
+        .spacing(10);
+
+        if self.can_cancel {
+            actions = actions.push(
+                button("Cancel")
+                    .on_press(DownloadMessage::CancelPressed)
+                    .padding([10, 20]),
+            );
+        }
+
+        if self.can_pause {
+            actions = actions.push(
+                button("Pause")
+                    .on_press(DownloadMessage::PauseDownloadPressed)
+                    .padding([10, 20]),
+            );
+        } else if let Some(name) = &self.paused_download {
+            actions = actions.push(
+                button(text(format!("Resume {}", name)))
+                    .on_press_maybe(
+                        (!self.is_downloading).then_some(DownloadMessage::ResumeDownloadPressed),
+                    )
+                    .padding([10, 20]),
+            );
+        }
+
+        if self.queue_paused {
+            actions = actions.push(
+                button("Resume queue")
+                    .on_press(DownloadMessage::ResumeQueuePressed)
+                    .padding([10, 20]),
+            );
+        } else if self.queued > 0 {
+            actions = actions.push(
+                button("Pause queue")
+                    .on_press(DownloadMessage::PauseQueuePressed)
+                    .padding([10, 20]),
+            );
+        }
+
+        content = content
+            .push(Space::new().height(Length::Fixed(20.0)))
+            .push(actions);
+
+        content = content.push(
+            row![
+                checkbox(self.compact)
+                    .label("Compact mode")
+                    .on_toggle(DownloadMessage::CompactToggled),
+                text("After download:").size(14),
+                pick_list(
+                    &PostDownloadAction::ALL[..],
+                    Some(self.post_download),
+                    DownloadMessage::PostDownloadSelected,
+                ),
+                button("Export settings").on_press(DownloadMessage::ExportSettingsPressed),
+            ]
+            .spacing(20),
         );
 
+        let folder = match &self.output_dir {
+            Some(dir) => dir.display().to_string(),
+            None => "No folder chosen".to_string(),
+        };
+        content = content.push(
+            row![
+                checkbox(self.use_output_dir)
+                    .label("Use default folder")
+                    .on_toggle(DownloadMessage::UseOutputDirToggled),
+                text(folder).size(14),
+                button("Choose folder...").on_press(DownloadMessage::ChooseOutputDirPressed),
+            ]
+            .spacing(20),
+        );
+        content = content.push(
+            row![
+                text("Proxy:").size(14),
+                text_input("http://host:8080 or socks5://host:1080", &self.proxy)
+                    .on_input(DownloadMessage::ProxyChanged)
+                    .on_submit(DownloadMessage::ProxySubmitted),
+                button("Apply").on_press(DownloadMessage::ProxySubmitted),
+            ]
+            .spacing(20),
+        );
+
+        if !self.history.is_empty() {
+            let rows = self
+                .history
+                .iter()
+                .fold(column![].spacing(5), |rows, entry| {
+                    rows.push(
+                        row![
+                            text(&entry.title).size(14).width(Length::Fill),
+                            button("Re-download")
+                                .on_press(DownloadMessage::RedownloadPressed(entry.url.clone())),
+                        ]
+                        .spacing(10),
+                    )
+                });
+            content = content
+                .push(Space::new().height(Length::Fixed(10.0)))
+                .push(text("History:").size(16))
+                .push(scrollable(rows).height(Length::Fill));
+        }
+
         content.padding(20).spacing(10).into()
     }
+
+    /// Format and bitrate pickers, or just the choice while a download runs
+    fn output_picker(&self) -> Element<'_, DownloadMessage> {
+        if self.is_downloading {
+            return text(format!("Format: {}, {}", self.format, self.quality))
+                .size(14)
+                .into();
+        }
+
+        let mut picker = row![
+            text("Format:").size(14),
+            pick_list(
+                &OutputFormat::ALL[..],
+                Some(self.format),
+                DownloadMessage::FormatChanged,
+            ),
+        ]
+        .spacing(10);
+        if self.format == OutputFormat::Mp3 {
+            picker = picker.push(text("Bitrate:").size(14)).push(pick_list(
+                &QUALITIES[..],
+                Some(self.quality),
+                DownloadMessage::BitrateChanged,
+            ));
+        }
+        picker.into()
+    }
+
+    fn compact_view(&self) -> Element<'_, DownloadMessage> {
+        row![
+            text_input("Enter YouTube URL...", &self.youtube_url)
+                .on_input(DownloadMessage::YoutubeUrlChanged)
+                .on_submit_maybe(self.download_message())
+                .padding(5),
+            button("Download").on_press_maybe(self.download_message()),
+            button("⤢").on_press(DownloadMessage::CompactToggled(false)),
+        ]
+        .padding(10)
+        .spacing(5)
+        .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout_follows_compact_setting() {
+        let mut view = DownloadView::default();
+        assert_eq!(view.layout(), Layout::Full);
+
+        view.update(DownloadMessage::CompactToggled(true));
+        assert_eq!(view.layout(), Layout::Compact);
+
+        view.update(DownloadMessage::CompactToggled(false));
+        assert_eq!(view.layout(), Layout::Full);
+    }
+
+    #[test]
+    fn test_non_mp3_format_drops_the_bitrate() {
+        let mut view = DownloadView::default();
+        view.update(DownloadMessage::BitrateChanged(AudioQuality::Kbps(320)));
+        assert_eq!(view.quality, AudioQuality::Kbps(320));
+
+        view.update(DownloadMessage::FormatChanged(OutputFormat::Opus));
+        assert_eq!(view.format, OutputFormat::Opus);
+        assert_eq!(view.quality, AudioQuality::Source);
+    }
+
+    #[test]
+    fn test_pulse_only_moves_an_indeterminate_bar() {
+        let mut view = DownloadView {
+            download_progress: 0.4,
+            ..Default::default()
+        };
+        view.pulse();
+        assert_eq!(view.download_progress, 0.4);
+
+        view.progress_indeterminate = true;
+        view.download_progress = 0.0;
+        let steps: Vec<f32> = (0..5)
+            .map(|_| {
+                view.pulse();
+                view.download_progress
+            })
+            .collect();
+        assert_eq!(steps, [0.25, 0.5, 0.75, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn test_compact_window_is_smaller() {
+        let full = Layout::Full.window_size();
+        let compact = Layout::Compact.window_size();
+        assert!(compact.width < full.width);
+        assert!(compact.height < full.height);
+    }
 }
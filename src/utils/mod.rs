@@ -1,4 +1,16 @@
-use std::time::{SystemTime, UNIX_EPOCH};
+mod repeat;
+
+use std::{
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+pub use repeat::RepeatCollapser;
+
+/// Per-user directory for the app's own state files, if the platform has one
+pub fn config_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("simple-mp3-downloader"))
+}
 
 /// Get current Unix timestamp in seconds
 pub fn get_timestamp() -> u64 {
@@ -21,33 +33,62 @@ pub fn sanitize_filename(filename: &str) -> String {
         .to_string()
 }
 
+/// Human-readable byte count, e.g. "1.5 MB"
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
 /// Extract video ID from various YouTube URL formats
 /// Supports:
 /// - https://www.youtube.com/watch?v=VIDEO_ID
 /// - https://youtu.be/VIDEO_ID
 /// - https://youtube.com/watch?v=VIDEO_ID
+/// - https://www.youtube.com/shorts/VIDEO_ID (also `/embed/`, `/v/` and `/live/`)
 /// - Direct video ID (returns as-is if valid)
 pub fn extract_video_id(input: &str) -> Option<String> {
     let input = input.trim();
 
     // If it looks like a raw video ID (11 characters, typical YouTube ID format)
-    if input.len() == 11
-        && input
-            .chars()
-            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
-    {
+    if is_video_id(input) {
         return Some(input.to_string());
     }
 
     // Try to parse as URL
     if let Ok(url) = url::Url::parse(input) {
+        // Hosts are compared case-insensitively
+        let host = url.host_str().map(str::to_ascii_lowercase);
+
         // Handle youtu.be short URLs
-        if url.host_str().map_or(false, |h| h.ends_with("youtu.be")) {
-            return url.path_segments()?.last().map(String::from);
+        if host.as_deref().is_some_and(|h| h.ends_with("youtu.be")) {
+            return url
+                .path_segments()?
+                .find(|segment| !segment.is_empty())
+                .filter(|id| is_video_id(id))
+                .map(String::from);
         }
 
         // Handle youtube.com watch URLs
-        if url.host_str().map_or(false, |h| h.ends_with("youtube.com")) {
+        if host.as_deref().is_some_and(|h| h.ends_with("youtube.com")) {
+            let mut segments = url.path_segments()?;
+            if let (Some("shorts" | "embed" | "v" | "live"), Some(id)) =
+                (segments.next(), segments.next())
+            {
+                return is_video_id(id).then(|| id.to_string());
+            }
+
             return url
                 .query_pairs()
                 .find(|(k, _)| k == "v")
@@ -58,6 +99,41 @@ pub fn extract_video_id(input: &str) -> Option<String> {
     None
 }
 
+/// 11 characters of the alphabet YouTube video IDs are made of
+fn is_video_id(candidate: &str) -> bool {
+    candidate.len() == 11
+        && candidate
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Canonical watch URL for a video link, dropping share and tracking params (`si=`, `t=`, ...)
+pub fn normalize_youtube_url(input: &str) -> Option<String> {
+    extract_video_id(input).map(|id| format!("https://www.youtube.com/watch?v={}", id))
+}
+
+/// Extract the playlist ID (`list=` param) from a YouTube URL
+pub fn extract_playlist_id(input: &str) -> Option<String> {
+    let url = url::Url::parse(input.trim()).ok()?;
+    let host = url.host_str()?.to_ascii_lowercase();
+    if !host.ends_with("youtube.com") && !host.ends_with("youtu.be") {
+        return None;
+    }
+
+    url.query_pairs()
+        .find(|(k, v)| k == "list" && !v.is_empty())
+        .map(|(_, v)| v.to_string())
+}
+
+/// Every video ID a link resolves to without asking YouTube
+///
+/// A watch URL into a playlist (`watch?v=ID&list=PL...`) gives just its own video. A bare
+/// `playlist?list=...` link gives none, since listing a playlist takes an API call; its
+/// ID is available from `extract_playlist_id`.
+pub fn extract_video_ids(input: &str) -> Vec<String> {
+    extract_video_id(input).into_iter().collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -74,6 +150,13 @@ mod tests {
         assert_eq!(sanitize_filename("normal-name.mp3"), "normal-name.mp3");
     }
 
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.5 KB");
+        assert_eq!(format_bytes(5 * 1024 * 1024), "5.0 MB");
+    }
+
     #[test]
     fn test_extract_video_id_from_watch_url() {
         let url = "https://www.youtube.com/watch?v=z0vCwGUZe1I";
@@ -86,12 +169,106 @@ mod tests {
         assert_eq!(extract_video_id(url), Some("z0vCwGUZe1I".to_string()));
     }
 
+    #[test]
+    fn test_extract_video_id_from_short_url_variants() {
+        let expected = Some("z0vCwGUZe1I".to_string());
+        assert_eq!(extract_video_id("https://youtu.be/z0vCwGUZe1I/"), expected);
+        assert_eq!(
+            extract_video_id("https://youtu.be/z0vCwGUZe1I?si=xyz&t=30"),
+            expected
+        );
+        assert_eq!(extract_video_id("https://youtu.be/"), None);
+        assert_eq!(extract_video_id("https://youtu.be"), None);
+        assert_eq!(extract_video_id("https://youtu.be/not-an-id"), None);
+    }
+
+    #[test]
+    fn test_extract_video_id_from_path_urls() {
+        let expected = Some("z0vCwGUZe1I".to_string());
+        for url in [
+            "https://www.youtube.com/shorts/z0vCwGUZe1I",
+            "https://youtube.com/shorts/z0vCwGUZe1I?feature=share",
+            "https://www.youtube.com/embed/z0vCwGUZe1I?start=30",
+            "https://www.youtube.com/v/z0vCwGUZe1I",
+            "https://www.youtube.com/live/z0vCwGUZe1I?si=abc123",
+            "https://m.youtube.com/shorts/z0vCwGUZe1I/",
+        ] {
+            assert_eq!(extract_video_id(url), expected, "{}", url);
+        }
+        assert_eq!(
+            extract_video_id("https://www.youtube.com/shorts/tooShort"),
+            None
+        );
+        assert_eq!(
+            extract_video_id("https://www.youtube.com/channel/UC1234567890"),
+            None
+        );
+    }
+
     #[test]
     fn test_extract_video_id_raw() {
         let video_id = "z0vCwGUZe1I";
         assert_eq!(extract_video_id(video_id), Some("z0vCwGUZe1I".to_string()));
     }
 
+    #[test]
+    fn test_extract_video_id_mixed_case() {
+        let expected = Some("z0vCwGUZe1I".to_string());
+        assert_eq!(
+            extract_video_id("HTTPS://WWW.YOUTUBE.COM/watch?v=z0vCwGUZe1I"),
+            expected
+        );
+        assert_eq!(
+            extract_video_id("https://www.YouTube.com/watch?v=z0vCwGUZe1I"),
+            expected
+        );
+        assert_eq!(extract_video_id("Https://YOUTU.BE/z0vCwGUZe1I"), expected);
+    }
+
+    #[test]
+    fn test_extract_playlist_id() {
+        assert_eq!(
+            extract_playlist_id("https://www.youtube.com/watch?v=z0vCwGUZe1I&list=PL123"),
+            Some("PL123".to_string())
+        );
+        assert_eq!(
+            extract_playlist_id("https://www.youtube.com/watch?v=z0vCwGUZe1I"),
+            None
+        );
+        assert_eq!(
+            extract_playlist_id("https://www.youtube.com/playlist?list=PL123"),
+            Some("PL123".to_string())
+        );
+    }
+
+    #[test]
+    fn test_extract_video_ids() {
+        let video = vec!["z0vCwGUZe1I".to_string()];
+        assert_eq!(
+            extract_video_ids("https://www.youtube.com/watch?v=z0vCwGUZe1I&list=PL123"),
+            video
+        );
+        assert_eq!(
+            extract_video_ids("https://www.youtube.com/watch?v=z0vCwGUZe1I"),
+            video
+        );
+        assert!(extract_video_ids("https://www.youtube.com/playlist?list=PL123").is_empty());
+    }
+
+    #[test]
+    fn test_normalize_youtube_url() {
+        let canonical = Some("https://www.youtube.com/watch?v=z0vCwGUZe1I".to_string());
+        assert_eq!(
+            normalize_youtube_url("https://youtu.be/z0vCwGUZe1I?si=abc123&t=42"),
+            canonical
+        );
+        assert_eq!(
+            normalize_youtube_url("https://m.youtube.com/watch?feature=share&v=z0vCwGUZe1I"),
+            canonical
+        );
+        assert_eq!(normalize_youtube_url("https://example.com"), None);
+    }
+
     #[test]
     fn test_extract_video_id_invalid() {
         assert_eq!(extract_video_id("not a url"), None);
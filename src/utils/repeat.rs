@@ -0,0 +1,53 @@
+/// Collapses identical consecutive messages into a single "(repeated N×)" line
+#[derive(Debug, Clone, Default)]
+pub struct RepeatCollapser {
+    last: Option<String>,
+    count: usize,
+}
+
+impl RepeatCollapser {
+    /// Record a message and return the line to display for it
+    pub fn push(&mut self, message: &str) -> String {
+        if self.last.as_deref() == Some(message) {
+            self.count += 1;
+            format!("{} (repeated {}×)", message, self.count)
+        } else {
+            self.last = Some(message.to_string());
+            self.count = 1;
+            message.to_string()
+        }
+    }
+
+    /// Forget the last message, so the next one is shown as-is
+    pub fn reset(&mut self) {
+        self.last = None;
+        self.count = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collapses_consecutive_repeats() {
+        let mut collapser = RepeatCollapser::default();
+        let lines: Vec<String> = ["a", "a", "a", "b", "a"]
+            .iter()
+            .map(|m| collapser.push(m))
+            .collect();
+
+        assert_eq!(
+            lines,
+            vec!["a", "a (repeated 2×)", "a (repeated 3×)", "b", "a"]
+        );
+    }
+
+    #[test]
+    fn test_reset_starts_over() {
+        let mut collapser = RepeatCollapser::default();
+        collapser.push("a");
+        collapser.reset();
+        assert_eq!(collapser.push("a"), "a");
+    }
+}